@@ -0,0 +1,106 @@
+// Benchmarks the byte level counting engine against the original line by line implementation and GNU wc.
+// Run with `cargo bench`, the input is generated so every run measures the same text.
+use std::fs;
+use std::io::{self, BufRead};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use count_lines_words_chars::{count_fields, Counts, Fields};
+
+// Size of the generated input and how many times each measurement is repeated
+const INPUT_SIZE: usize = 64 * 1024 * 1024;
+const RUNS: usize = 5;
+
+fn main() {
+    let ascii = generate_text(INPUT_SIZE, &["the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog\t"]);
+    let unicode = generate_text(INPUT_SIZE, &["héllo", "wörld", "世界", "😀", "naïve", "plain", "words", "a"]);
+
+    let lines_only = Fields { lines: true, ..Fields::default() };
+    let wc_default = Fields { lines: true, words: true, bytes: true, ..Fields::default() };
+    let everything = Fields::all();
+
+    for (name, text) in [("ascii", &ascii), ("unicode", &unicode)] {
+        println!("\n{} input, {} MiB", name, text.len() / (1024 * 1024));
+
+        // write the input to disk too so wc reads the same bytes
+        let path = std::env::temp_dir().join(format!("count_lwc_bench_{}.txt", name));
+        fs::write(&path, text).expect("[ERROR] Failed to write benchmark input.");
+
+        for (label, fields, wc_flag) in [("-l", &lines_only, "-l"), ("default", &wc_default, "-lwc"), ("-lwmcL", &everything, "-lwmcL")] {
+            let engine = measure(text.len(), || count_fields(text.as_slice(), fields).unwrap());
+            let baseline = measure(text.len(), || count_lines_baseline(text.as_slice(), fields).unwrap());
+            print!("  {:<8} engine {:>8.1} MiB/s   line by line {:>8.1} MiB/s", label, engine, baseline);
+            match measure_wc(&path, wc_flag, text.len()) {
+                Some(wc) => println!("   wc {:>8.1} MiB/s", wc),
+                None => println!("   wc not available"),
+            }
+        }
+
+        let _ = fs::remove_file(&path);
+    }
+}
+
+// Function to build some text out of the given words, a few per line
+fn generate_text(size: usize, words: &[&str]) -> Vec<u8> {
+    // a small xorshift keeps the output the same on every run without pulling in a random crate
+    let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+    let mut text = Vec::with_capacity(size + 64);
+    while text.len() < size {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        for i in 0..(seed % 12) {
+            if i > 0 {
+                text.push(b' ');
+            }
+            text.extend_from_slice(words[((seed >> (i * 3)) % words.len() as u64) as usize].as_bytes());
+        }
+        text.push(b'\n');
+    }
+    text
+}
+
+// Function to time a counting function, returning the best throughput seen in MiB per second
+fn measure(size: usize, mut count: impl FnMut() -> Counts) -> f64 {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        std::hint::black_box(count());
+        best = best.min(start.elapsed());
+    }
+    size as f64 / (1024.0 * 1024.0) / best.as_secs_f64()
+}
+
+// Function to time GNU wc on the same input, None when it can't be run
+fn measure_wc(path: &std::path::Path, flag: &str, size: usize) -> Option<f64> {
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let status = Command::new("wc").arg(flag).arg(path).env("LC_ALL", "C.UTF-8").output().ok()?.status;
+        if !status.success() {
+            return None;
+        }
+        best = best.min(start.elapsed());
+    }
+    Some(size as f64 / (1024.0 * 1024.0) / best.as_secs_f64())
+}
+
+// The original implementation: one String per line from BufRead::lines(), decoding every line
+// whatever was asked for. Kept here as the baseline the engine is measured against, it never
+// worked out the longest line so its -lwmcL numbers flatter it a little
+fn count_lines_baseline(reader: impl io::Read, fields: &Fields) -> io::Result<Counts> {
+    let mut counts = Counts::default();
+    for line in io::BufReader::new(reader).lines() {
+        let line = line?;
+        if fields.lines {
+            counts.lines += 1;
+        }
+        if fields.chars {
+            counts.chars += line.chars().count();
+        }
+        if fields.words {
+            counts.words += line.split_whitespace().count();
+        }
+    }
+    Ok(counts)
+}
@@ -0,0 +1,242 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::{count_named, report_order, wanted, Entry, FileResult, Options};
+
+// The compression formats -z/--decompress recognises
+#[derive(Clone, Copy)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+// Function to recognise a compressed stream from the magic bytes it starts with
+pub fn compression(sample: &[u8]) -> Option<Compression> {
+    if sample.starts_with(&[0x1f, 0x8b]) {
+        Some(Compression::Gzip)
+    } else if sample.starts_with(b"BZh") {
+        Some(Compression::Bzip2)
+    } else if sample.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Some(Compression::Xz)
+    } else if sample.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some(Compression::Zstd)
+    } else {
+        None
+    }
+}
+
+// Function to wrap a compressed stream in the decoder that gives back what was compressed,
+// files made by concatenating several compressed streams are read all the way through
+pub fn decoder<'a>(compression: Compression, reader: impl BufRead + 'a) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        Compression::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(reader)),
+        Compression::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(reader)),
+        Compression::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)),
+        Compression::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+    })
+}
+
+// Function to open a file, decompressing it on the way if it is compressed
+fn open_decompressed(path: &Path) -> io::Result<Box<dyn Read>> {
+    let mut reader = io::BufReader::new(fs::File::open(path)?);
+    match compression(reader.fill_buf()?) {
+        Some(compression) => decoder(compression, reader),
+        None => Ok(Box::new(reader)),
+    }
+}
+
+// The file name extensions --archives looks inside
+const EXTENSIONS: &[&str] = &["zip", "tar", "tgz", "tbz2", "txz", "tzst", "tar.gz", "tar.bz2", "tar.xz", "tar.zst"];
+
+// Function to tell whether --archives should look inside a file, going by its name
+pub fn is_archive(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    EXTENSIONS.iter().any(|extension| name.strip_suffix(extension).is_some_and(|stem| stem.ends_with('.')))
+}
+
+// Function to make globs matching the same names is_archive does, so a directory walk with --include lets
+// archives through to have their members picked out by it
+pub fn globs() -> Vec<String> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| {
+            // is_archive doesn't care about case, so every letter matches either
+            let pattern: String = extension
+                .chars()
+                .map(|c| if c.is_ascii_alphabetic() { format!("[{}{}]", c, c.to_ascii_uppercase()) } else { c.to_string() })
+                .collect();
+            format!("*.{}", pattern)
+        })
+        .collect()
+}
+
+// Function to count every file in a tar or zip archive as if it were a file under the archive's path.
+// The archive itself shows up as a directory at the given depth, with a directory for every folder in it
+pub fn archive_entries(path: &Path, depth: usize, options: &Options) -> Vec<Entry> {
+    // an archive at --max-depth is left unopened, the same as a directory there
+    if options.counter.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+        let result = FileResult::NotWalked("deeper than --max-depth".to_string());
+        return vec![Entry { path: path.to_path_buf(), depth, size: 0, is_stdin: false, result: Some(result) }];
+    }
+
+    // the members are picked out by the same rules as the files in a directory, with globs matched from
+    // wherever the walk started, which is the archive itself when it was given on its own
+    let root = path.ancestors().nth(depth).unwrap_or(path);
+    let overrides = match options.counter.overrides(root) {
+        Ok(overrides) => overrides,
+        Err(e) => return vec![Entry { path: path.to_path_buf(), depth, size: 0, is_stdin: false, result: Some(FileResult::Failed(e)) }],
+    };
+    // members that are left out aren't even read
+    let wanted = |name: &Path| wanted(root, &path.join(name), false, &overrides, options);
+
+    let mut members = Vec::new();
+    let is_zip = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"));
+    let result = if is_zip { read_zip(path, options, wanted, &mut members) } else { read_tar(path, options, wanted, &mut members) };
+    // an archive we can't open at all is reported like a file we couldn't read
+    if let Err(e) = result {
+        if members.is_empty() {
+            return vec![Entry { path: path.to_path_buf(), depth, size: 0, is_stdin: false, result: Some(FileResult::Failed(e)) }];
+        }
+        // one we got part way through keeps what was counted and reports where it broke off
+        members.push((PathBuf::new(), 0, FileResult::Failed(e)));
+    }
+    let max_depth = options.counter.max_depth.unwrap_or(usize::MAX);
+
+    let mut entries = vec![Entry { path: path.to_path_buf(), depth, size: 0, is_stdin: false, result: None }];
+    let mut directories = BTreeSet::new();
+    for (name, size, result) in members {
+        let member_path = path.join(&name);
+        let member_depth = depth + name.components().count().max(1);
+        // archives don't always list their folders, so every folder a member is in gets an entry of its own. A folder
+        // at --max-depth is reported in place of what's in it, the same as a directory the walk won't go into
+        for folder in name.ancestors().skip(1).filter(|folder| !folder.as_os_str().is_empty()) {
+            let folder_depth = depth + folder.components().count();
+            if folder_depth <= max_depth && directories.insert(folder.to_path_buf()) {
+                let result = (folder_depth == max_depth).then(|| FileResult::NotWalked("deeper than --max-depth".to_string()));
+                entries.push(Entry { path: path.join(folder), depth: folder_depth, size: 0, is_stdin: false, result });
+            }
+        }
+        if member_depth <= max_depth {
+            entries.push(Entry { path: member_path, depth: member_depth, size, is_stdin: false, result: Some(result) });
+        }
+    }
+
+    // put every folder right before what's in it, the same order the directory walk gives
+    entries[1..].sort_by(report_order);
+    entries
+}
+
+// Function to count the regular files in a tar archive that are wanted, the archive may be compressed as a whole
+fn read_tar(path: &Path, options: &Options, wanted: impl Fn(&Path) -> bool, members: &mut Vec<(PathBuf, u64, FileResult)>) -> io::Result<()> {
+    let mut archive = tar::Archive::new(open_decompressed(path)?);
+    for member in archive.entries()? {
+        let member = member?;
+        if !member.header().entry_type().is_file() {
+            continue;
+        }
+        // tar names can start with ./ or climb out with .., only the folder and file names themselves are kept
+        let name: PathBuf = member.path()?.components().filter(|part| matches!(part, Component::Normal(_))).collect();
+        if name.as_os_str().is_empty() || !wanted(&name) {
+            continue;
+        }
+        let size = member.size();
+        members.push((name.clone(), size, count_named(&name, member, options)));
+    }
+    Ok(())
+}
+
+// Function to count the files in a zip archive that are wanted
+fn read_zip(path: &Path, options: &Options, wanted: impl Fn(&Path) -> bool, members: &mut Vec<(PathBuf, u64, FileResult)>) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?).map_err(io::Error::other)?;
+    for index in 0..archive.len() {
+        let member = archive.by_index(index).map_err(io::Error::other)?;
+        if member.is_dir() {
+            continue;
+        }
+        // names that would climb out of the archive, like ../../etc/passwd, are left out
+        let Some(name) = member.enclosed_name().filter(|name| wanted(name)) else {
+            continue;
+        };
+        let size = member.size();
+        members.push((name.clone(), size, count_named(&name, member, options)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{options, relative_paths, temp_dir};
+    use std::io::Write;
+
+    fn write_tar(path: &Path, files: &[(&str, &str)]) {
+        let mut archive = tar::Builder::new(fs::File::create(path).unwrap());
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            archive.append_data(&mut header, name, contents.as_bytes()).unwrap();
+        }
+        archive.finish().unwrap();
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &str)]) {
+        let mut archive = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, contents) in files {
+            archive.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            archive.write_all(contents.as_bytes()).unwrap();
+        }
+        archive.finish().unwrap();
+    }
+
+    #[test]
+    fn archives_get_past_include_in_a_directory_walk() {
+        let dir = temp_dir("archive-include");
+        write_tar(&dir.join("ar.tar"), &[("a.rs", "fn a() {}\n"), ("b.md", "hi\n")]);
+        write_zip(&dir.join("ar.ZIP"), &[("src/c.rs", "fn c() {}\n"), ("d.md", "hi\n")]);
+        fs::write(dir.join("e.md"), "hi\n").unwrap();
+        let mut options = options();
+        options.archives = true;
+        options.counter.include = vec!["*.rs".to_string()];
+
+        let entries = crate::process_path(&dir, &options).unwrap();
+        assert_eq!(relative_paths(&dir, &entries), ["", "ar.ZIP", "ar.ZIP/src", "ar.ZIP/src/c.rs", "ar.tar", "ar.tar/a.rs"]);
+
+        // without --archives they're files like any other, which --include leaves out
+        options.archives = false;
+        assert_eq!(relative_paths(&dir, &crate::process_path(&dir, &options).unwrap()), [""]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn archive_members_are_picked_by_exclude_hidden_and_max_depth() {
+        let dir = temp_dir("archive-members");
+        let files = [("a.rs", "fn a() {}\n"), ("b.md", "hi\n"), (".hidden", "hi\n"), ("x/y/deep.rs", "fn d() {}\n")];
+        write_tar(&dir.join("ar.tar"), &files);
+        let archive = dir.join("ar.tar");
+        let mut options = options();
+        options.counter.exclude = vec!["*.md".to_string()];
+
+        let entries = archive_entries(&archive, 0, &options);
+        assert_eq!(relative_paths(&dir, &entries), ["ar.tar", "ar.tar/a.rs", "ar.tar/x", "ar.tar/x/y", "ar.tar/x/y/deep.rs"]);
+
+        options.counter.hidden = true;
+        options.counter.max_depth = Some(2);
+        let entries = archive_entries(&archive, 0, &options);
+        assert_eq!(relative_paths(&dir, &entries), ["ar.tar", "ar.tar/.hidden", "ar.tar/a.rs", "ar.tar/x", "ar.tar/x/y"]);
+        assert!(matches!(entries[4].result, Some(FileResult::NotWalked(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn archives_are_recognised_by_name() {
+        assert!(is_archive(Path::new("a.tar.gz")));
+        assert!(is_archive(Path::new("A.TGZ")));
+        assert!(!is_archive(Path::new("a.gz")));
+        assert!(!is_archive(Path::new("tar")));
+    }
+}
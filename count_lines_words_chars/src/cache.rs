@@ -0,0 +1,350 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use count_lines_words_chars::code::{self, CodeCounts};
+use count_lines_words_chars::Counts;
+
+use crate::{count_path, FileResult, Options};
+
+// Bumped whenever what goes in the cache changes, so an old cache file is thrown away rather than misread
+const CACHE_VERSION: u64 = 1;
+
+// The counts from earlier runs, kept on disk so a file that hasn't changed since doesn't have to be read again
+pub struct Cache {
+    file: PathBuf,
+    // whether a file whose modification time changed but whose size didn't is checked by its contents
+    // before being counted again, which saves recounting everything after a fresh checkout
+    hash_contents: bool,
+    files: Mutex<HashMap<PathBuf, Cached>>,
+    // whether anything has changed since the cache was loaded or last saved, there's nothing to save otherwise
+    changed: AtomicBool,
+}
+
+// What we knew about a file the last time it was counted
+struct Cached {
+    size: u64,
+    modified: u128,
+    hash: Option<u64>,
+    result: CachedResult,
+}
+
+// The results worth remembering, skipped and failed files are cheap to find out about again
+enum CachedResult {
+    Text(Counts),
+    Binary(Counts),
+    Code(CodeCounts),
+}
+
+// Function to work out where the cache lives when --cache-dir doesn't say, None when there's no home directory
+pub fn default_cache_dir() -> Option<PathBuf> {
+    match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("count_lwc")),
+        _ => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("count_lwc")),
+    }
+}
+
+impl Cache {
+    // Load the cache for runs that count things the same way, the settings pick which file in the directory
+    // that is since different flags give different counts. A cache that is missing or can't be read starts empty
+    pub fn open(dir: &Path, settings: &str, hash_contents: bool) -> Cache {
+        let file = dir.join(format!("counts-{:016x}.json", fnv1a(settings.as_bytes(), FNV_OFFSET)));
+        let files = fs::read(&file)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok())
+            .filter(|document| document["version"] == CACHE_VERSION)
+            .and_then(|document| document["files"].as_object().map(from_json))
+            .unwrap_or_default();
+        Cache { file, hash_contents, files: Mutex::new(files), changed: AtomicBool::new(false) }
+    }
+
+    // Count a file, or give back what it counted to last time if it hasn't changed since
+    pub fn count(&self, path: &Path, options: &Options) -> FileResult {
+        let (Ok(key), Ok(metadata)) = (std::path::absolute(path), fs::metadata(path)) else {
+            return count_path(path, options);
+        };
+        let size = metadata.len();
+        let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map(|time| time.as_nanos());
+
+        if let Some(modified) = modified {
+            let hash = {
+                let files = self.files.lock().unwrap();
+                match files.get(&key).filter(|cached| cached.size == size) {
+                    Some(cached) if cached.modified == modified => return cached.result.to_file_result(path),
+                    Some(cached) => cached.hash,
+                    None => None,
+                }
+            };
+            // touched but the same size, it's only worth counting again if what's in it changed. The file is
+            // read without holding the lock so the other threads can carry on
+            if self.hash_contents && hash.is_some() && hash == hash_file(path).ok() {
+                if let Some(cached) = self.files.lock().unwrap().get_mut(&key) {
+                    cached.modified = modified;
+                    self.changed.store(true, Ordering::Relaxed);
+                    return cached.result.to_file_result(path);
+                }
+            }
+        }
+
+        let result = count_path(path, options);
+        let cached = match &result {
+            FileResult::Text(counts) => Some(CachedResult::Text(*counts)),
+            FileResult::Binary(counts) => Some(CachedResult::Binary(*counts)),
+            FileResult::Code(_, counts) => Some(CachedResult::Code(*counts)),
+            _ => None,
+        };
+        match (cached, modified) {
+            (Some(result), Some(modified)) => {
+                let hash = if self.hash_contents { hash_file(path).ok() } else { None };
+                self.files.lock().unwrap().insert(key, Cached { size, modified, hash, result });
+                self.changed.store(true, Ordering::Relaxed);
+            }
+            // a file that failed or is skipped this time mustn't be given last time's counts next time
+            _ => {
+                if self.files.lock().unwrap().remove(&key).is_some() {
+                    self.changed.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        result
+    }
+
+    // Write the cache back to disk if anything in it changed, leaving out files that have since been deleted. It's written
+    // to a temporary file first so a run that's interrupted, or another run at the same time, never leaves half a cache
+    pub fn save(&self) -> io::Result<()> {
+        if !self.changed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let files = self.files.lock().unwrap();
+        let mut entries = Map::new();
+        for (path, cached) in files.iter().filter(|(path, _)| path.exists()) {
+            entries.insert(path.to_string_lossy().into_owned(), cached.to_json());
+        }
+        let document = json!({ "version": CACHE_VERSION, "files": entries });
+
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temporary = self.file.with_extension(format!("json.{}", std::process::id()));
+        fs::write(&temporary, serde_json::to_vec(&document)?)?;
+        fs::rename(&temporary, &self.file)?;
+        self.changed.store(false, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Where the cache is kept, for error messages
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+}
+
+impl CachedResult {
+    // Function to turn a remembered result back into what counting the file would have given
+    fn to_file_result(&self, path: &Path) -> FileResult {
+        match self {
+            CachedResult::Text(counts) => FileResult::Text(*counts),
+            CachedResult::Binary(counts) => FileResult::Binary(*counts),
+            // the language comes from the file name, which is part of the key
+            CachedResult::Code(counts) => match code::language_for(path) {
+                Some(language) => FileResult::Code(language, *counts),
+                None => FileResult::Skipped,
+            },
+        }
+    }
+}
+
+impl Cached {
+    // Function to write a cache entry as JSON, counts that weren't asked for are simply zero
+    fn to_json(&self) -> Value {
+        let (kind, counts) = match &self.result {
+            CachedResult::Text(counts) => ("text", counts_to_json(counts)),
+            CachedResult::Binary(counts) => ("binary", counts_to_json(counts)),
+            CachedResult::Code(counts) => ("code", json!([counts.lines, counts.code, counts.comments, counts.blanks])),
+        };
+        // nanoseconds since 1970 need more than the 64 bits serde_json's numbers go up to
+        json!({ "size": self.size, "modified": self.modified.to_string(), "hash": self.hash, "kind": kind, "counts": counts })
+    }
+
+    // Function to read a cache entry back, None for one that doesn't look the way we wrote it
+    fn from_json(value: &Value) -> Option<Cached> {
+        let counts: Vec<usize> = value["counts"].as_array()?.iter().map(|n| n.as_u64().map(|n| n as usize)).collect::<Option<_>>()?;
+        let result = match (value["kind"].as_str()?, counts.as_slice()) {
+            ("text", &[lines, words, chars, graphemes, bytes, max_line_length]) => {
+                CachedResult::Text(Counts { lines, words, chars, graphemes, bytes, max_line_length })
+            }
+            ("binary", &[lines, words, chars, graphemes, bytes, max_line_length]) => {
+                CachedResult::Binary(Counts { lines, words, chars, graphemes, bytes, max_line_length })
+            }
+            ("code", &[lines, code, comments, blanks]) => CachedResult::Code(CodeCounts { lines, code, comments, blanks }),
+            _ => return None,
+        };
+        Some(Cached {
+            size: value["size"].as_u64()?,
+            modified: value["modified"].as_str()?.parse().ok()?,
+            hash: value["hash"].as_u64(),
+            result,
+        })
+    }
+}
+
+// Function to read every entry of a cache file, leaving out any that can't be read
+fn from_json(files: &Map<String, Value>) -> HashMap<PathBuf, Cached> {
+    files.iter().filter_map(|(path, value)| Some((PathBuf::from(path), Cached::from_json(value)?))).collect()
+}
+
+// Function to write a file's counts as a list in the order Counts has them
+fn counts_to_json(counts: &Counts) -> Value {
+    json!([counts.lines, counts.words, counts.chars, counts.graphemes, counts.bytes, counts.max_line_length])
+}
+
+// The FNV-1a hash, which unlike the standard library's hasher gives the same answer on every build,
+// so the cache file name and content hashes stay the same from one version of count_lwc to the next
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// Function to carry an FNV-1a hash on over some more bytes
+fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for byte in bytes {
+        hash = (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+// Function to hash everything in a file
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut hash = FNV_OFFSET;
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hash),
+            n => hash = fnv1a(&buffer[..n], hash),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{options, temp_dir};
+    use std::time::{Duration, SystemTime};
+
+    // Function to replace what's in a file while giving it the modification time we choose
+    fn write_at(path: &Path, contents: &str, modified: SystemTime) {
+        fs::write(path, contents).unwrap();
+        fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    fn lines(result: FileResult) -> usize {
+        match result {
+            FileResult::Text(counts) => counts.lines,
+            _ => panic!("expected text counts"),
+        }
+    }
+
+    #[test]
+    fn an_unchanged_file_is_not_read_again() {
+        let dir = temp_dir("cache-hit");
+        let file = dir.join("a.txt");
+        let then = SystemTime::now() - Duration::from_secs(60);
+        write_at(&file, "a\n", then);
+        let cache = Cache::open(&dir.join("cache"), "settings", false);
+        let options = options();
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+
+        // same size and time, so the counts from last time are given back without looking at what's in it
+        write_at(&file, "ab", then);
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+
+        // a new modification time or size means counting it again
+        write_at(&file, "ab", then + Duration::from_secs(1));
+        assert_eq!(lines(cache.count(&file, &options)), 0);
+        write_at(&file, "a\nb\n", then + Duration::from_secs(1));
+        assert_eq!(lines(cache.count(&file, &options)), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hashing_keeps_counts_for_a_file_that_was_only_touched() {
+        let dir = temp_dir("cache-hash");
+        let file = dir.join("a.txt");
+        let then = SystemTime::now() - Duration::from_secs(60);
+        write_at(&file, "a\n", then);
+        let cache = Cache::open(&dir.join("cache"), "settings", true);
+        let options = options();
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+
+        write_at(&file, "a\n", then + Duration::from_secs(1));
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+        // the new time was remembered along with the counts
+        write_at(&file, "ab", then + Duration::from_secs(1));
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+        // but what's in it is looked at once the time changes again
+        write_at(&file, "ab", then + Duration::from_secs(2));
+        assert_eq!(lines(cache.count(&file, &options)), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn counts_are_kept_between_runs_with_the_same_settings() {
+        let dir = temp_dir("cache-save");
+        let file = dir.join("a.txt");
+        let then = SystemTime::now() - Duration::from_secs(60);
+        write_at(&file, "a\n", then);
+        let options = options();
+        let cache = Cache::open(&dir.join("cache"), "settings", false);
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+        cache.save().unwrap();
+
+        write_at(&file, "ab", then);
+        let cache = Cache::open(&dir.join("cache"), "settings", false);
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+        let cache = Cache::open(&dir.join("cache"), "other settings", false);
+        assert_eq!(lines(cache.count(&file, &options)), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_written_when_nothing_changed() {
+        let dir = temp_dir("cache-unchanged");
+        let file = dir.join("a.txt");
+        write_at(&file, "a\n", SystemTime::now() - Duration::from_secs(60));
+        let cache = Cache::open(&dir.join("cache"), "settings", false);
+        cache.save().unwrap();
+        assert!(!dir.join("cache").exists());
+
+        cache.count(&file, &options());
+        cache.save().unwrap();
+        let written = fs::metadata(cache.file()).unwrap().modified().unwrap();
+        // a second run that finds everything as it was leaves the file alone
+        let cache = Cache::open(&dir.join("cache"), "settings", false);
+        cache.count(&file, &options());
+        assert!(!cache.changed.load(Ordering::Relaxed));
+        cache.save().unwrap();
+        assert_eq!(fs::metadata(cache.file()).unwrap().modified().unwrap(), written);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_file_that_fails_is_forgotten() {
+        let dir = temp_dir("cache-forget");
+        let file = dir.join("a.txt");
+        let then = SystemTime::now() - Duration::from_secs(60);
+        write_at(&file, "a\n", then);
+        let mut options = options();
+        let cache = Cache::open(&dir.join("cache"), "settings", false);
+        assert_eq!(lines(cache.count(&file, &options)), 1);
+
+        // binary files are an error now, so the next run mustn't be handed the counts from before
+        options.binary = crate::BinaryMode::Error;
+        write_at(&file, "\0\0", then + Duration::from_secs(1));
+        assert!(matches!(cache.count(&file, &options), FileResult::Failed(_)));
+        assert!(cache.files.lock().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use std::ops::Add;
+use std::path::Path;
+
+/// How a programming language writes its comments and strings, enough to tell code lines from comment lines
+pub struct Language {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    // whole file names for files that don't have an extension, like Makefile
+    file_names: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    // whether block comments can contain other block comments, like Rust's /* /* */ */
+    nested: bool,
+    quotes: &'static [char],
+}
+
+// Shorthands for the comment styles most languages borrow from each other
+const C_LINE: &[&str] = &["//"];
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+const HASH_LINE: &[&str] = &["#"];
+const XML_BLOCK: &[(&str, &str)] = &[("<!--", "-->")];
+const DOUBLE_QUOTES: &[char] = &['"'];
+const BOTH_QUOTES: &[char] = &['"', '\''];
+
+// The languages --code knows about, looked up by file extension or file name
+const LANGUAGES: &[Language] = &[
+    Language { name: "C", extensions: &["c"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "C Header", extensions: &["h"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "C++", extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "C#", extensions: &["cs"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "CSS", extensions: &["css"], file_names: &[], line_comments: &[], block_comments: C_BLOCK, nested: false, quotes: BOTH_QUOTES },
+    Language { name: "Go", extensions: &["go"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "Haskell", extensions: &["hs"], file_names: &[], line_comments: &["--"], block_comments: &[("{-", "-}")], nested: true, quotes: DOUBLE_QUOTES },
+    Language { name: "HTML", extensions: &["htm", "html"], file_names: &[], line_comments: &[], block_comments: XML_BLOCK, nested: false, quotes: BOTH_QUOTES },
+    Language { name: "Java", extensions: &["java"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "JavaScript", extensions: &["cjs", "js", "jsx", "mjs"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: &['"', '\'', '`'] },
+    Language { name: "JSON", extensions: &["json"], file_names: &[], line_comments: &[], block_comments: &[], nested: false, quotes: DOUBLE_QUOTES },
+    Language { name: "Kotlin", extensions: &["kt", "kts"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: true, quotes: DOUBLE_QUOTES },
+    Language { name: "Lua", extensions: &["lua"], file_names: &[], line_comments: &["--"], block_comments: &[("--[[", "]]")], nested: false, quotes: BOTH_QUOTES },
+    Language { name: "Makefile", extensions: &["mk"], file_names: &["Makefile", "makefile", "GNUmakefile"], line_comments: HASH_LINE, block_comments: &[], nested: false, quotes: &[] },
+    Language { name: "Markdown", extensions: &["md", "markdown"], file_names: &[], line_comments: &[], block_comments: XML_BLOCK, nested: false, quotes: &[] },
+    Language { name: "PHP", extensions: &["php"], file_names: &[], line_comments: &["//", "#"], block_comments: C_BLOCK, nested: false, quotes: BOTH_QUOTES },
+    // docstrings are counted as comments, the same way tokei does
+    Language { name: "Python", extensions: &["py", "pyw"], file_names: &[], line_comments: HASH_LINE, block_comments: &[("\"\"\"", "\"\"\""), ("'''", "'''")], nested: false, quotes: BOTH_QUOTES },
+    Language { name: "Ruby", extensions: &["rb"], file_names: &["Rakefile", "Gemfile"], line_comments: HASH_LINE, block_comments: &[("=begin", "=end")], nested: false, quotes: BOTH_QUOTES },
+    Language { name: "Rust", extensions: &["rs"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: true, quotes: DOUBLE_QUOTES },
+    Language { name: "Shell", extensions: &["bash", "sh", "zsh"], file_names: &[], line_comments: HASH_LINE, block_comments: &[], nested: false, quotes: BOTH_QUOTES },
+    Language { name: "SQL", extensions: &["sql"], file_names: &[], line_comments: &["--"], block_comments: C_BLOCK, nested: false, quotes: &['\''] },
+    Language { name: "Swift", extensions: &["swift"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: true, quotes: DOUBLE_QUOTES },
+    Language { name: "TOML", extensions: &["toml"], file_names: &[], line_comments: HASH_LINE, block_comments: &[], nested: false, quotes: BOTH_QUOTES },
+    Language { name: "TypeScript", extensions: &["ts", "tsx", "mts", "cts"], file_names: &[], line_comments: C_LINE, block_comments: C_BLOCK, nested: false, quotes: &['"', '\'', '`'] },
+    Language { name: "XML", extensions: &["xml", "svg", "xsd", "xsl"], file_names: &[], line_comments: &[], block_comments: XML_BLOCK, nested: false, quotes: &[] },
+    Language { name: "YAML", extensions: &["yaml", "yml"], file_names: &[], line_comments: HASH_LINE, block_comments: &[], nested: false, quotes: BOTH_QUOTES },
+];
+
+/// How many lines of a file are code, comments or blank
+#[derive(Default, Clone, Copy)]
+pub struct CodeCounts {
+    pub lines: usize,
+    pub code: usize,
+    pub comments: usize,
+    pub blanks: usize,
+}
+
+impl Add for CodeCounts {
+    type Output = CodeCounts;
+
+    fn add(self, other: CodeCounts) -> CodeCounts {
+        CodeCounts {
+            lines: self.lines + other.lines,
+            code: self.code + other.code,
+            comments: self.comments + other.comments,
+            blanks: self.blanks + other.blanks,
+        }
+    }
+}
+
+/// Work out a file's language from its name, None when it isn't one we know
+pub fn language_for(path: &Path) -> Option<&'static Language> {
+    let file_name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+    LANGUAGES.iter().find(|language| {
+        language.file_names.contains(&file_name)
+            || extension.as_deref().is_some_and(|extension| language.extensions.contains(&extension))
+    })
+}
+
+// Which block comment we are inside at the end of a line, and how deeply when they nest
+struct BlockComment {
+    end: &'static str,
+    depth: usize,
+}
+
+/// Sort every line of some source text into code, comment or blank. A line with any code on it
+/// counts as code even if it ends in a comment, a line with only comments on it counts as a comment
+pub fn count_code(text: &str, language: &Language) -> CodeCounts {
+    let mut counts = CodeCounts::default();
+    let mut block: Option<BlockComment> = None;
+
+    for line in text.lines() {
+        counts.lines += 1;
+        let line = line.trim();
+        if line.is_empty() {
+            counts.blanks += 1;
+            continue;
+        }
+
+        let mut has_code = false;
+        let mut has_comment = false;
+        // the quote of the string we're in, strings don't carry on past the end of the line
+        let mut string: Option<char> = None;
+        let mut i = 0;
+        while i < line.len() {
+            let rest = &line[i..];
+            let c = rest.chars().next().unwrap();
+
+            if let Some(comment) = &mut block {
+                has_comment = true;
+                if rest.starts_with(comment.end) {
+                    i += comment.end.len();
+                    comment.depth -= 1;
+                    if comment.depth == 0 {
+                        block = None;
+                    }
+                    continue;
+                }
+                // a nested comment has to be closed as many times as it was opened
+                if language.nested {
+                    if let Some((start, _)) = language.block_comments.iter().find(|(start, end)| *end == comment.end && rest.starts_with(start)) {
+                        comment.depth += 1;
+                        i += start.len();
+                        continue;
+                    }
+                }
+                i += c.len_utf8();
+                continue;
+            }
+
+            if let Some(quote) = string {
+                has_code = true;
+                if c == '\\' {
+                    // skip whatever is escaped, including an escaped quote
+                    i += 1;
+                    i += line[i..].chars().next().map_or(0, char::len_utf8);
+                    continue;
+                }
+                if c == quote {
+                    string = None;
+                }
+                i += c.len_utf8();
+                continue;
+            }
+
+            // comment markers only count outside of strings, so "http://" is still code. Block comments
+            // go first since some start with a line comment marker, like Lua's --[[
+            if let Some((start, end)) = language.block_comments.iter().find(|(start, _)| rest.starts_with(start)) {
+                has_comment = true;
+                block = Some(BlockComment { end, depth: 1 });
+                i += start.len();
+                continue;
+            }
+            if language.line_comments.iter().any(|marker| rest.starts_with(marker)) {
+                has_comment = true;
+                break;
+            }
+
+            if language.quotes.contains(&c) {
+                string = Some(c);
+            }
+            if !c.is_whitespace() {
+                has_code = true;
+            }
+            i += c.len_utf8();
+        }
+
+        if has_code {
+            counts.code += 1;
+        } else if has_comment {
+            counts.comments += 1;
+        } else {
+            counts.blanks += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(file_name: &str, text: &str) -> (usize, usize, usize, usize) {
+        let counts = count_code(text, language_for(Path::new(file_name)).unwrap());
+        (counts.lines, counts.code, counts.comments, counts.blanks)
+    }
+
+    #[test]
+    fn lines_are_code_comments_or_blank() {
+        let text = "fn main() {\n    // comment\n    let x = 1; // trailing\n\n    /* block\n       still */\n}\n";
+        assert_eq!(count("main.rs", text), (7, 3, 3, 1));
+    }
+
+    #[test]
+    fn comment_markers_in_strings_are_code() {
+        let text = "let url = \"http://example.com\";\nlet s = \"/* not a comment\";\nx();\n";
+        assert_eq!(count("main.rs", text), (3, 3, 0, 0));
+        // an escaped quote doesn't end the string
+        assert_eq!(count("main.rs", "let s = \"\\\" // still a string\";\n"), (1, 1, 0, 0));
+    }
+
+    #[test]
+    fn nested_comments_only_nest_where_the_language_allows() {
+        let text = "/* a /* b */ c */\n";
+        assert_eq!(count("main.rs", text), (1, 0, 1, 0));
+        // in C the first */ ends the comment, which leaves c */ as code
+        assert_eq!(count("main.c", text), (1, 1, 0, 0));
+    }
+
+    #[test]
+    fn block_comments_win_over_line_comments() {
+        let text = "--[[ a block\nstill in it ]]\nprint(1) -- done\n";
+        assert_eq!(count("init.lua", text), (3, 1, 2, 0));
+    }
+
+    #[test]
+    fn docstrings_count_as_comments() {
+        let text = "def f():\n    \"\"\"Does\n    things.\"\"\"\n    return 1\n";
+        assert_eq!(count("f.py", text), (4, 2, 2, 0));
+    }
+
+    #[test]
+    fn languages_are_found_by_extension_or_file_name() {
+        assert_eq!(language_for(Path::new("src/MAIN.RS")).unwrap().name, "Rust");
+        assert_eq!(language_for(Path::new("Makefile")).unwrap().name, "Makefile");
+        assert!(language_for(Path::new("notes.txt")).is_none());
+    }
+}
@@ -1,141 +1,1442 @@
-use clap::{Arg, ArgAction, Command};
+use clap::{Parser, ValueEnum};
+use encoding_rs::Encoding;
+use ignore::overrides::Override;
+use ignore::WalkState;
+use std::cmp::Ordering;
 use std::fs;
-use std::io::{self, BufRead};
-use std::path::Path;
-use walkdir::WalkDir;
+use std::io::{self, BufRead, Read};
+use std::ops::Add;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+
+mod archive;
+mod cache;
+mod git;
+mod output;
+mod watch;
+
+use count_lines_words_chars::code::{self, CodeCounts, Language};
+use count_lines_words_chars::readability::{self, Readability};
+use count_lines_words_chars::stats::{self, Vocabulary, WordStats};
+use count_lines_words_chars::text::{self, Content};
+use count_lines_words_chars::{Counter, Counts, Fields};
+use cache::Cache;
+use output::{CodeFile, DiffFile, Format, Group, Output, ProseFile, StatsFile};
 
 // Constants for version and app name
 const VERSION: &str = "1.0.0";
 const APP_NAME: &str = "cwl";
 
+// Which counts to print and in what order, how wide each count column is and which files the walk picks up
+struct Options {
+    columns: Vec<Field>,
+    // what actually gets counted and which files the walk picks up, more can be counted than
+    // is printed when --sort or a filter needs it
+    counter: Counter,
+    width: usize,
+    total: TotalMode,
+    binary: BinaryMode,
+    jobs: usize,
+    code: bool,
+    group_by: Option<GroupBy>,
+    sort: Option<SortKey>,
+    top: Option<usize>,
+    min_lines: Option<usize>,
+    max_lines: Option<usize>,
+    min_bytes: Option<usize>,
+    max_bytes: Option<usize>,
+    one_file_system: bool,
+    fail_fast: bool,
+    decompress: bool,
+    archives: bool,
+    // counts from earlier runs of files found walking a directory, unless --no-cache
+    cache: Option<Cache>,
+    // the git revision --rev counts the files as they were at
+    rev: Option<String>,
+    // how --stats picks out words, None when it isn't working out word statistics
+    stats: Option<Vocabulary>,
+    top_words: usize,
+    readability: bool,
+    // strip Markdown before --stats or --readability look at the words
+    markdown: bool,
+}
+
+// One of the counts that can be printed, --fields lists them in the order they should appear
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Field {
+    Lines,
+    Words,
+    Chars,
+    Graphemes,
+    Bytes,
+    MaxLineLength,
+}
+
+impl Field {
+    // The name the count goes by in structured output
+    fn name(self) -> &'static str {
+        match self {
+            Field::Lines => "lines",
+            Field::Words => "words",
+            Field::Chars => "chars",
+            Field::Graphemes => "graphemes",
+            Field::Bytes => "bytes",
+            Field::MaxLineLength => "max_line_length",
+        }
+    }
+
+    // The count itself
+    fn count(self, counts: &Counts) -> usize {
+        match self {
+            Field::Lines => counts.lines,
+            Field::Words => counts.words,
+            Field::Chars => counts.chars,
+            Field::Graphemes => counts.graphemes,
+            Field::Bytes => counts.bytes,
+            Field::MaxLineLength => counts.max_line_length,
+        }
+    }
+}
+
+// When to print the total line, these are the same choices wc gives for --total
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum TotalMode {
+    Auto,
+    Always,
+    Never,
+    Only,
+}
+
+// What --group-by sums the counts up by
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum GroupBy {
+    #[value(name = "ext")]
+    Extension,
+    #[value(name = "dir")]
+    Directory,
+    Language,
+}
+
+// Which column --sort orders the results by, names go A to Z and numbers go largest first
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum SortKey {
+    Name,
+    Files,
+    Size,
+    Lines,
+    Words,
+    Chars,
+    Graphemes,
+    Bytes,
+    MaxLineLength,
+}
+
+// How --words splits text into words
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum WordMode {
+    // anything between whitespace is a word, like wc
+    Whitespace,
+    // the Unicode word boundary rules (UAX #29)
+    Unicode,
+}
+
+// What to do with files that look binary rather than text
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum BinaryMode {
+    Skip,
+    CountBytes,
+    Error,
+}
+
+// What happened when we tried to count a single file
+enum FileResult {
+    Text(Counts),
+    Binary(Counts),
+    // a source file counted by --code
+    Code(&'static Language, CodeCounts),
+    // the words in a file counted by --stats
+    Stats(WordStats),
+    // the sentences, words and syllables --readability scores a file by
+    Readability(Readability),
+    Skipped,
+    // something the walk found but didn't go into, along with why
+    NotWalked(String),
+    Failed(io::Error),
+}
+
+// Something the walk found, kept until everything is counted so it can be reported in sorted order
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    size: u64,
+    is_stdin: bool,
+    // None for directories
+    result: Option<FileResult>,
+}
+
+// Counts summed over a group of files along with how many files and bytes on disk went into them
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    counts: Counts,
+    files: usize,
+    size: u64,
+}
+
+impl Add for Totals {
+    type Output = Totals;
+
+    fn add(self, other: Totals) -> Totals {
+        Totals {
+            counts: self.counts + other.counts,
+            files: self.files + other.files,
+            size: self.size + other.size,
+        }
+    }
+}
+
+// The command-line arguments, the flags mirror GNU wc
+#[derive(Parser)]
+#[command(name = APP_NAME, version = VERSION, about = "Count lines, words, and characters in files or directories")]
+struct Args {
+    #[arg(help = "Paths to files or directories, with none or - standard input is read")]
+    path: Vec<PathBuf>,
+
+    #[arg(long, value_name = "FILE", conflicts_with = "path",
+        help = "Read the paths to count from FILE, separated by NUL characters, - reads them from standard input")]
+    files0_from: Option<String>,
+
+    #[arg(short = 'c', long, help = "Get the byte count")]
+    bytes: bool,
+
+    #[arg(short = 'm', long, help = "Get the character count")]
+    chars: bool,
+
+    #[arg(short, long, value_name = "MODE", value_enum, num_args = 0..=1, require_equals = true,
+        default_missing_value = "whitespace",
+        help = "Get the word count, --words=unicode finds words by Unicode's rules instead of between whitespace")]
+    words: Option<WordMode>,
+
+    #[arg(long, help = "Get the count of user-perceived characters (extended grapheme clusters)")]
+    graphemes: bool,
+
+    #[arg(short, long, help = "Get the line count")]
+    lines: bool,
+
+    #[arg(short = 'L', long, help = "Get the display width of the longest line")]
+    max_line_length: bool,
+
+    #[arg(long, value_name = "LIST", value_delimiter = ',',
+        conflicts_with_all = ["bytes", "chars", "graphemes", "lines", "max_line_length"],
+        help = "Counts to print and their order, as a comma separated list of lines, words, chars, graphemes, bytes and \
+                max-line-length. --words=unicode still picks how words are counted")]
+    fields: Vec<Field>,
+
+    #[arg(long, value_name = "WHEN", value_enum, default_value_t = TotalMode::Auto,
+        help = "When to print the total line")]
+    total: TotalMode,
+
+    #[arg(long, value_name = "FORMAT", value_enum, default_value_t = Format::Plain,
+        help = "Output format: plain (like wc), json, csv, tsv or table")]
+    format: Format,
+
+    #[arg(long, conflicts_with = "format", help = "Show the directory structure as a tree with counts for every node")]
+    tree: bool,
+
+    #[arg(long, value_name = "MODE", value_enum, default_value_t = BinaryMode::CountBytes,
+        help = "What to do with binary files: skip them, count only their bytes, or report an error")]
+    binary: BinaryMode,
+
+    #[arg(long, value_name = "ENCODING", value_parser = parse_encoding,
+        help = "Read files in this encoding (latin1, utf-16, utf-16be, windows-1252, ...) instead of detecting it")]
+    encoding: Option<&'static Encoding>,
+
+    #[arg(long, value_name = "GLOB", help = "Only count files matching the glob, can be given more than once")]
+    include: Vec<String>,
+
+    #[arg(long, value_name = "GLOB", help = "Skip files and directories matching the glob, can be given more than once")]
+    exclude: Vec<String>,
+
+    #[arg(long, help = "Include hidden files and directories")]
+    hidden: bool,
+
+    #[arg(long, help = "Don't respect .gitignore and .ignore files")]
+    no_ignore: bool,
+
+    #[arg(short, long, value_name = "N", help = "Number of threads to walk and count with, defaults to one per CPU")]
+    jobs: Option<usize>,
+
+    #[arg(long, conflicts_with_all = ["tree", "bytes", "chars", "graphemes", "words", "lines", "max_line_length", "fields"],
+        help = "Count code, comment and blank lines in source files, by file and by language")]
+    code: bool,
+
+    #[arg(long, value_name = "KEY", value_enum, conflicts_with_all = ["tree", "code"],
+        help = "Sum the counts up by file extension, directory or language instead of listing every file")]
+    group_by: Option<GroupBy>,
+
+    #[arg(long, value_name = "COLUMN", value_enum, conflicts_with_all = ["tree", "code"],
+        help = "List files on their own sorted by a column (or sort the --group-by summary), numbers are sorted largest first")]
+    sort: Option<SortKey>,
+
+    #[arg(long, value_name = "N", conflicts_with_all = ["tree", "code"],
+        help = "Only show the first N files (or groups) once they are sorted, by lines unless --sort says otherwise")]
+    top: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "Leave out files with fewer than N lines")]
+    min_lines: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "Leave out files with more than N lines")]
+    max_lines: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "Leave out files smaller than N bytes")]
+    min_bytes: Option<usize>,
+
+    #[arg(long, value_name = "N", help = "Leave out files larger than N bytes")]
+    max_bytes: Option<usize>,
+
+    #[arg(long, value_name = "N",
+        help = "Only go N directories deep, 1 counts just the files directly inside each directory given")]
+    max_depth: Option<usize>,
+
+    #[arg(long, help = "Follow symbolic links while walking, links that loop back on themselves are skipped")]
+    follow_symlinks: bool,
+
+    #[arg(long, help = "Don't go into directories on a different file system from the directory given")]
+    one_file_system: bool,
+
+    #[arg(long, help = "Stop at the first file that can't be counted instead of carrying on with the rest")]
+    fail_fast: bool,
+
+    #[arg(short = 'z', long, help = "Count what's inside gzip, bzip2, xz and zstd compressed files rather than the compressed bytes")]
+    decompress: bool,
+
+    #[arg(long, help = "Count the files inside tar and zip archives as if they were in a directory named after the archive")]
+    archives: bool,
+
+    #[arg(long, help = "Count every file found in a directory again instead of reusing the counts of files that haven't changed")]
+    no_cache: bool,
+
+    #[arg(long, value_name = "DIR", conflicts_with = "no_cache",
+        help = "Keep the counts of files found in directories here, defaults to $XDG_CACHE_HOME/count_lwc or ~/.cache/count_lwc")]
+    cache_dir: Option<PathBuf>,
+
+    #[arg(long, conflicts_with = "no_cache",
+        help = "Check a cached file's contents when only its modification time changed, instead of counting it again")]
+    cache_hash: bool,
+
+    #[arg(long, conflicts_with = "fail_fast",
+        help = "Keep running and count everything again whenever a file is created, changed or removed, \
+                along with how much the total has changed since the start")]
+    watch: bool,
+
+    #[arg(long, value_name = "REV", conflicts_with_all = ["watch", "archives"],
+        help = "Count the files as they are in a git revision (a commit, branch or tag) instead of on disk")]
+    rev: Option<String>,
+
+    #[arg(long, value_name = "A..B", conflicts_with_all = ["rev", "watch", "archives", "code", "tree", "group_by", "sort", "top"],
+        help = "Show how much each count changed in every file that changed between two git revisions, \
+                a side left out stands for HEAD")]
+    diff: Option<String>,
+
+    #[arg(long, group = "prose",
+        conflicts_with_all = ["tree", "code", "group_by", "sort", "top", "diff", "bytes", "chars", "graphemes", "lines",
+        "max_line_length", "fields"],
+        help = "Show word statistics for every file instead of counts: the most frequent words, how many different \
+                words there are, the average word length and the average number of words on a line")]
+    stats: bool,
+
+    #[arg(long, value_name = "N", default_value_t = 10, requires = "stats", help = "How many of the most frequent words --stats lists")]
+    top_words: usize,
+
+    #[arg(long, value_name = "LIST", requires = "stats",
+        help = "Leave these words out of the --stats frequencies: english for a built in list of common English words, \
+                or a file with one word per line. Can be given more than once")]
+    stop_words: Vec<String>,
+
+    #[arg(long, requires = "stats", help = "Count words that only differ in case as the same word in --stats")]
+    fold_case: bool,
+
+    #[arg(long, group = "prose",
+        conflicts_with_all = ["tree", "code", "group_by", "sort", "top", "diff", "bytes", "chars", "graphemes", "words", "lines",
+        "max_line_length", "fields"],
+        help = "Show how readable every file is instead of counts: its sentences, words and syllables, the Flesch reading \
+                ease and the Flesch-Kincaid grade level")]
+    readability: bool,
+
+    #[arg(long, requires = "prose",
+        help = "Read files as Markdown for --stats and --readability, leaving code, link URLs and markup out of the prose")]
+    markdown: bool,
+}
+
 // Entry point of the program
-fn main() {
-    // Setting up the command-line argument parser
-    let matches = Command::new(APP_NAME)
-        .version(VERSION)
-        .about("Count lines, words, and characters in files or directories")
-        .arg(Arg::new("path")
-            .required(true)
-            .help("Path to a file or a directory"))
-        .arg(Arg::new("chars")
-            .short('c')
-            .long("chars")
-            .action(ArgAction::SetTrue)
-            .help("Get the character count"))
-        .arg(Arg::new("words")
-            .short('w')
-            .long("words")
-            .action(ArgAction::SetTrue)
-            .help("Get the word count"))
-        .arg(Arg::new("lines")
-            .short('l')
-            .long("lines")
-            .action(ArgAction::SetTrue)
-            .help("Get the line count"))
-        .get_matches();
-
-    // Retrieve the path argument and the flags for counting chars, words, and lines
-    let path = matches.get_one::<String>("path").unwrap();
-    let count_chars = *matches.get_one::<bool>("chars").unwrap_or(&false) || (!matches.contains_id("chars") && !matches.contains_id("words") && !matches.contains_id("lines"));
-    let count_words = *matches.get_one::<bool>("words").unwrap_or(&false) || (!matches.contains_id("chars") && !matches.contains_id("words") && !matches.contains_id("lines"));
-    let count_lines = *matches.get_one::<bool>("lines").unwrap_or(&false) || (!matches.contains_id("chars") && !matches.contains_id("words") && !matches.contains_id("lines"));
-
-    // Process the given path
-    if let Err(e) = process_path(Path::new(path), count_chars, count_words, count_lines) {
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    // --fields picks the counts and their order, otherwise the flags pick them and they come in wc's order.
+    // With neither we print what wc prints by default: lines, words and bytes
+    let columns = if !args.fields.is_empty() {
+        let mut columns: Vec<Field> = Vec::new();
+        for field in args.fields {
+            if !columns.contains(&field) {
+                columns.push(field);
+            }
+        }
+        columns
+    } else {
+        let flags = [
+            (args.lines, Field::Lines),
+            (args.words.is_some(), Field::Words),
+            (args.chars, Field::Chars),
+            (args.graphemes, Field::Graphemes),
+            (args.bytes, Field::Bytes),
+            (args.max_line_length, Field::MaxLineLength),
+        ];
+        let chosen: Vec<Field> = flags.iter().filter(|(on, _)| *on).map(|(_, field)| *field).collect();
+        if chosen.is_empty() {
+            vec![Field::Lines, Field::Words, Field::Bytes]
+        } else {
+            chosen
+        }
+    };
+    let format = if args.tree { Format::Tree } else { args.format };
+    // the top files are the ones with the most lines unless we're told otherwise
+    let sort = args.sort.or(if args.top.is_some() { Some(SortKey::Lines) } else { None });
+
+    // sorting or filtering on a count that isn't printed still needs it counted
+    let counted = |field: Field| columns.contains(&field);
+    let fields = Fields {
+        lines: counted(Field::Lines) || sort == Some(SortKey::Lines) || args.min_lines.is_some() || args.max_lines.is_some(),
+        words: counted(Field::Words) || sort == Some(SortKey::Words),
+        chars: counted(Field::Chars) || sort == Some(SortKey::Chars),
+        graphemes: counted(Field::Graphemes) || sort == Some(SortKey::Graphemes),
+        bytes: counted(Field::Bytes),
+        max_line_length: counted(Field::MaxLineLength) || sort == Some(SortKey::MaxLineLength),
+        unicode_words: args.words == Some(WordMode::Unicode),
+    };
+    let mut options = Options {
+        columns,
+        counter: Counter {
+            fields,
+            encoding: args.encoding,
+            hidden: args.hidden,
+            no_ignore: args.no_ignore,
+            include: args.include,
+            exclude: args.exclude,
+            max_depth: args.max_depth,
+            follow_symlinks: args.follow_symlinks,
+        },
+        width: 1,
+        total: args.total,
+        binary: args.binary,
+        jobs: args
+            .jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+            .max(1),
+        code: args.code,
+        group_by: args.group_by,
+        sort,
+        top: args.top,
+        min_lines: args.min_lines,
+        max_lines: args.max_lines,
+        min_bytes: args.min_bytes,
+        max_bytes: args.max_bytes,
+        one_file_system: args.one_file_system,
+        fail_fast: args.fail_fast,
+        decompress: args.decompress,
+        archives: args.archives,
+        cache: None,
+        rev: args.rev,
+        stats: None,
+        top_words: args.top_words,
+        readability: args.readability,
+        markdown: args.markdown,
+    };
+    if args.stats {
+        let mut vocabulary = Vocabulary { unicode_words: fields.unicode_words, fold_case: args.fold_case, ..Vocabulary::default() };
+        for list in &args.stop_words {
+            match read_stop_words(list) {
+                Ok(words) => vocabulary.stop_words.extend(words),
+                Err(e) => {
+                    report_error(Path::new(list), &e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        options.stats = Some(vocabulary);
+    }
+    // a file counts differently depending on these, so each combination of them gets a cache of its own.
+    // --stats keeps every word of every file, which is more than is worth keeping between runs, and
+    // --readability is cheap enough next to reading the files that it isn't worth keeping either
+    if !args.no_cache && !args.stats && !args.readability {
+        let settings = format!(
+            "fields={}{}{}{}{}{} unicode-words={} binary={} encoding={} code={} decompress={}",
+            u8::from(fields.lines),
+            u8::from(fields.words),
+            u8::from(fields.chars),
+            u8::from(fields.graphemes),
+            u8::from(fields.bytes),
+            u8::from(fields.max_line_length),
+            fields.unicode_words,
+            args.binary.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default(),
+            args.encoding.map_or("detect", |encoding| encoding.name()),
+            args.code,
+            args.decompress,
+        );
+        options.cache = args.cache_dir.or_else(cache::default_cache_dir).map(|dir| Cache::open(&dir, &settings, args.cache_hash));
+    }
+
+    // Work out what to count: the paths listed in --files0-from, the paths given, or standard input when there are none
+    let paths: Vec<PathBuf> = match &args.files0_from {
+        Some(list) => match read_files0_from(list) {
+            Ok(paths) => paths,
+            Err(e) => {
+                report_error(Path::new(list), &e);
+                return ExitCode::FAILURE;
+            }
+        },
+        // a revision has no standard input, the whole repository we're in is counted instead
+        None if args.path.is_empty() && (options.rev.is_some() || args.diff.is_some()) => vec![PathBuf::from(".")],
+        // like wc, standard input read without being asked for by name is printed with no name
+        None if args.path.is_empty() => vec![PathBuf::new()],
+        None => args.path,
+    };
+
+    // --watch keeps counting the same files over and over, there's only one go at standard input
+    if args.watch && paths.iter().any(|path| path.as_os_str().is_empty() || path.as_os_str() == "-") {
+        eprintln!("[ERROR] --watch needs files or directories to watch, it can't watch standard input");
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(range) = &args.diff {
+        return report_diff(&paths, range, format, &options);
+    }
+
+    let run = run(&paths, format, &mut options);
+    if args.watch {
+        if let Err(e) = watch::watch(&paths, format, &mut options, run.totals) {
+            eprintln!("[ERROR] {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    if run.failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// What counting all the paths came to: the grand total, which --watch compares each run with, and whether anything failed
+struct Run {
+    totals: Totals,
+    failed: bool,
+}
+
+// Function to count everything under every path and report it
+fn run(paths: &[PathBuf], format: Format, options: &mut Options) -> Run {
+    // Count everything under every path first, the column width depends on the sizes of all the files found.
+    // Like wc, a path that can't be read is reported and we carry on with the rest, but still exit with a failure
+    let mut inputs = Vec::new();
+    let mut failed = false;
+    for path in paths {
+        match process_path(path, options) {
+            Ok(entries) => {
+                let first_error = entries.iter().find_map(|entry| match &entry.result {
+                    Some(FileResult::Failed(e)) => Some((&entry.path, e)),
+                    _ => None,
+                });
+                if let Some((error_path, e)) = first_error {
+                    failed = true;
+                    // --fail-fast stops at the first error without reporting anything else
+                    if options.fail_fast {
+                        report_error(error_path, e);
+                        return Run { totals: Totals::default(), failed };
+                    }
+                }
+                inputs.push((path.clone(), entries));
+            }
+            Err(e) => {
+                report_error(path, &e);
+                failed = true;
+                if options.fail_fast {
+                    return Run { totals: Totals::default(), failed };
+                }
+            }
+        }
+    }
+    // the cache only saves work next time, so not being able to write it doesn't fail this run
+    if let Some(cache) = &options.cache {
+        if let Err(e) = cache.save() {
+            eprintln!("[ERROR] couldn't save the cache to \"{}\": {}", cache.file().display(), e);
+        }
+    }
+    // files the --min and --max filters rule out are dropped as if they'd never been found
+    for entry in inputs.iter_mut().flat_map(|(_, entries)| entries.iter_mut()) {
+        if !passes_filters(entry, options) {
+            entry.result = Some(FileResult::Skipped);
+        }
+    }
+    let totals = file_totals(inputs.iter().flat_map(|(_, entries)| entries));
+
+    // --code breaks the counts down by language and --group-by replaces the listing of
+    // every file with a summary of each group, otherwise every file gets a row
+    let result = if options.stats.is_some() {
+        report_stats(inputs, format, options)
+    } else if options.readability {
+        report_readability(inputs, format, options)
+    } else if options.code {
+        report_code(inputs, format, options)
+    } else if let Some(group_by) = options.group_by {
+        options.width = number_width(inputs.iter().flat_map(|(_, entries)| entries), options);
+        report_groups(inputs, group_by, format, options)
+    } else {
+        report_files(inputs, format, options)
+    };
+    if let Err(e) = result {
         eprintln!("[ERROR] {}", e);
+        failed = true;
+    }
+    Run { totals, failed }
+}
+
+// Function to report how the counts changed between two revisions in every file under every path, for --diff
+fn report_diff(paths: &[PathBuf], range: &str, format: Format, options: &Options) -> ExitCode {
+    let mut files: Vec<DiffFile> = Vec::new();
+    let mut failed = false;
+    for path in paths {
+        match git::diff_files(range, path, options) {
+            Ok(changed) => files.extend(changed),
+            Err(e) => {
+                report_error(path, &e);
+                failed = true;
+                if options.fail_fast {
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+    if let Err(e) = output::write_diff(&files, format, options) {
+        eprintln!("[ERROR] {}", e);
+        failed = true;
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// Function to report every file that was counted, directory by directory or in --sort order, and the grand total
+fn report_files(inputs: Vec<(PathBuf, Vec<Entry>)>, format: Format, options: &mut Options) -> io::Result<()> {
+    let mut output = Output::new(format);
+    let mut totals = Totals::default();
+    if options.sort.is_some() {
+        // sorting lists the files on their own rather than directory by directory, each still shown under
+        // the path it was found in. Like --group-by, the total covers every file and not just the --top ones
+        totals = file_totals(inputs.iter().flat_map(|(_, entries)| entries));
+        let ranked = rank_entries(inputs, options);
+        options.width = number_width(ranked.iter().map(|(_, entry)| entry), options);
+        for (root, entry) in ranked {
+            output.set_root(&root);
+            report_entries(vec![entry], options, &mut output);
+        }
+    } else {
+        // each path is reported relative to itself, then we finish with the grand total across all of them
+        options.width = number_width(inputs.iter().flat_map(|(_, entries)| entries), options);
+        for (path, entries) in inputs {
+            output.set_root(&path);
+            totals = totals + report_entries(entries, options, &mut output);
+        }
     }
+    if output::show_total(options, totals.files) {
+        // like wc, --total=only prints the bare numbers without a label
+        let label = if options.total == TotalMode::Only { "" } else { "total" };
+        output.total(&totals, label, options);
+    }
+    output.finish(options)
+}
+
+// Function to print an error about a path to stderr, every error about a file looks the same
+fn report_error(path: &Path, error: &io::Error) {
+    // like wc, standard input read without being named has a name of its own in errors
+    let name = if path.as_os_str().is_empty() { "standard input".to_string() } else { path.display().to_string() };
+    eprintln!("[ERROR] \"{}\": {}", name, error);
 }
 
-// Function to process the given path (file or directory)
-fn process_path(path: &Path, count_chars: bool, count_words: bool, count_lines: bool) -> io::Result<()> {
-    if path.is_file() {
-        process_file(path, count_chars, count_words, count_lines)?;
-    } else if path.is_dir() {
-        process_directory(path, count_chars, count_words, count_lines)?;
+// Function to read a --stop-words list, either the built in English one or a file with a word on each line
+fn read_stop_words(list: &str) -> io::Result<Vec<String>> {
+    if list == "english" {
+        return Ok(stats::ENGLISH_STOP_WORDS.iter().map(|word| word.to_string()).collect());
+    }
+    let contents = fs::read_to_string(list)?;
+    Ok(contents.lines().map(|word| word.trim().to_lowercase()).filter(|word| !word.is_empty()).collect())
+}
+
+// Function to look up an --encoding label, accepting the same names browsers do
+fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("unknown encoding \"{}\"", label))
+}
+
+// Function to read the NUL separated list of paths --files0-from points at
+fn read_files0_from(list: &str) -> io::Result<Vec<PathBuf>> {
+    let mut contents = Vec::new();
+    if list == "-" {
+        io::stdin().lock().read_to_end(&mut contents)?;
     } else {
-        eprintln!("[ERROR] Invalid path \"{}\"", path.display());
+        fs::File::open(list)?.read_to_end(&mut contents)?;
+    }
+
+    let mut paths = Vec::new();
+    for name in contents.split(|&byte| byte == 0) {
+        // the list normally ends with a NUL, which leaves an empty name at the very end
+        if name.is_empty() {
+            continue;
+        }
+        match path_from_bytes(name) {
+            Some(path) => paths.push(path),
+            None => eprintln!("[ERROR] \"{}\": skipping a path that isn't valid UTF-8", list),
+        }
+    }
+    Ok(paths)
+}
+
+// Function to turn a file name from --files0-from into a path, on unix any bytes but NUL make a valid file name
+#[cfg(unix)]
+fn path_from_bytes(name: &[u8]) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+    Some(PathBuf::from(std::ffi::OsStr::from_bytes(name)))
+}
+
+// Everywhere else paths have to be text
+#[cfg(not(unix))]
+fn path_from_bytes(name: &[u8]) -> Option<PathBuf> {
+    std::str::from_utf8(name).ok().map(PathBuf::from)
+}
+
+// Function to work out the column width the same way wc does, from the files that get a row of counts
+fn number_width<'a>(entries: impl Iterator<Item = &'a Entry>, options: &Options) -> usize {
+    let files: Vec<&Entry> =
+        entries.filter(|entry| matches!(entry.result, Some(FileResult::Text(_) | FileResult::Binary(_)))).collect();
+    // a single count for a single file is printed without any padding
+    if files.len() == 1 && options.columns.len() == 1 {
+        return 1;
     }
-    Ok(())
+
+    // otherwise the columns are as wide as the total size of every file we read,
+    // standard input has no size up front so wc never goes narrower than 7 when reading it
+    let total_size: u64 = files.iter().map(|entry| entry.size).sum();
+    let minimum = if files.iter().any(|entry| entry.is_stdin) { 7 } else { 1 };
+    total_size.to_string().len().max(minimum)
 }
 
-// Function to process a single file
-fn process_file(path: &Path, count_chars: bool, count_words: bool, count_lines: bool) -> io::Result<()> {
+// Function to process the given path (file, directory or standard input), counting every file in it
+fn process_path(path: &Path, options: &Options) -> io::Result<Vec<Entry>> {
+    // an empty path is standard input read because no paths were given
+    if path.as_os_str().is_empty() || path == Path::new("-") {
+        // the language comes from the file name, which standard input doesn't have
+        if options.code {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "--code can't tell the language of standard input"));
+        }
+        let result = match &options.stats {
+            Some(vocabulary) => count_stats(vocabulary, io::stdin().lock(), options),
+            None if options.readability => count_readability(io::stdin().lock(), options),
+            None => count_stream(io::stdin().lock(), options),
+        };
+        // there's nothing to stat, so the size is however much we read
+        let size = match &result {
+            FileResult::Text(counts) | FileResult::Binary(counts) => counts.bytes as u64,
+            _ => 0,
+        };
+        return Ok(vec![Entry { path: path.to_path_buf(), depth: 0, size, is_stdin: true, result: Some(result) }]);
+    }
+
+    // --rev reads the files from git, they needn't be on disk at all
+    if let Some(rev) = &options.rev {
+        return git::revision_entries(rev, path, options);
+    }
+
+    // anything that isn't a directory is read like a file, which lets wc style paths like /dev/stdin work
     let metadata = fs::metadata(path)?;
-    if metadata.len() == 0 {
-        println!("File \"{}\" is empty", path.file_name().unwrap().to_string_lossy());
-        return Ok(());
+    if metadata.is_dir() {
+        return process_directory(path, options);
+    }
+    if options.archives && archive::is_archive(path) {
+        return Ok(archive::archive_entries(path, 0, options));
+    }
+    let result = count_path(path, options);
+    Ok(vec![Entry { path: path.to_path_buf(), depth: 0, size: metadata.len(), is_stdin: false, result: Some(result) }])
+}
+
+// Function to count a single file the way the options ask for
+fn count_path(path: &Path, options: &Options) -> FileResult {
+    // --code doesn't need to open anything that isn't in a language it knows
+    if options.code && code::language_for(path).is_none() {
+        return FileResult::Skipped;
+    }
+    match fs::File::open(path) {
+        Ok(file) => count_named(path, file, options),
+        Err(e) => FileResult::Failed(e),
+    }
+}
+
+// Function to count something with a name, a file or a member of an archive, the way the options ask for
+fn count_named(path: &Path, reader: impl Read, options: &Options) -> FileResult {
+    if let Some(vocabulary) = &options.stats {
+        return count_stats(vocabulary, reader, options);
+    }
+    if options.readability {
+        return count_readability(reader, options);
+    }
+    if !options.code {
+        return count_stream(reader, options);
+    }
+    match code::language_for(path) {
+        Some(language) => count_code(language, reader, options),
+        None => FileResult::Skipped,
     }
+}
+
+// Function to count whatever a reader holds, working out first whether it is binary and how its text is encoded
+fn count_stream(stream: impl Read, options: &Options) -> FileResult {
+    let mut reader = io::BufReader::with_capacity(text::SAMPLE_SIZE, stream);
 
-    let file = match fs::File::open(path) {
-        Ok(file) => file,
-        Err(e) => {
-            println!("File name:\"{}\", error: {}", path.file_name().unwrap().to_string_lossy(), e);
-            return Ok(());
+    // peek at the start of the file before counting anything so binary files never get read as text
+    // and we know which encoding to decode it with
+    let content = match reader.fill_buf() {
+        // a compressed file is counted by what it holds, which can't look any less like text than it does now
+        Ok(sample) if options.decompress && archive::compression(sample).is_some() => {
+            let compression = archive::compression(sample).unwrap();
+            return match archive::decoder(compression, reader) {
+                Ok(decoder) => count_stream(decoder, options),
+                Err(e) => FileResult::Failed(e),
+            };
         }
+        Ok(sample) => text::detect(sample, options.counter.encoding),
+        Err(e) => return FileResult::Failed(e),
     };
+    let Content::Text(encoding) = content else {
+        return match options.binary {
+            BinaryMode::Skip => FileResult::Skipped,
+            BinaryMode::Error => FileResult::Failed(binary_error()),
+            BinaryMode::CountBytes => match io::copy(&mut reader, &mut io::sink()) {
+                Ok(bytes) => FileResult::Binary(Counts { bytes: bytes as usize, ..Counts::default() }),
+                Err(e) => FileResult::Failed(e),
+            },
+        };
+    };
+    match options.counter.count_text(reader, encoding) {
+        Ok(counts) => FileResult::Text(counts),
+        Err(e) => FileResult::Failed(e),
+    }
+}
 
-    let reader = io::BufReader::new(file);
+// Function to build the error --binary=error gives a binary file
+fn binary_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "binary file")
+}
 
-    let mut char_count = 0;
-    let mut word_count = 0;
-    let mut line_count = 0;
+// Function to read the whole of a file as text for the counts that need all of it at once, None when it looks
+// binary and --binary doesn't make that an error. Only the bytes of a binary file could be counted, which
+// isn't what these counts are about, so one is left out even with --binary=count-bytes
+fn read_text(mut reader: impl Read, options: &Options) -> io::Result<Option<String>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
 
-    for line in reader.lines() {
-        let line = match line {
-            Ok(line) => line,
-            Err(e) => {
-                println!("File name:\"{}\", error: {}", path.file_name().unwrap().to_string_lossy(), e);
-                return Ok(());
+    // the same checks count_stream makes, the files these counts are for are small enough to decode in one go
+    let sample = &bytes[..bytes.len().min(text::SAMPLE_SIZE)];
+    if let Some(compression) = archive::compression(sample).filter(|_| options.decompress) {
+        return read_text(archive::decoder(compression, bytes.as_slice())?, options);
+    }
+    match text::detect(sample, options.counter.encoding) {
+        Content::Text(encoding) => Ok(Some(text::decode(&bytes, encoding))),
+        Content::Binary => match options.binary {
+            BinaryMode::Error => Err(binary_error()),
+            BinaryMode::Skip | BinaryMode::CountBytes => Ok(None),
+        },
+    }
+}
+
+// Function to count the code, comment and blank lines in a source file, one that looks binary is skipped unless --binary=error
+fn count_code(language: &'static Language, reader: impl Read, options: &Options) -> FileResult {
+    match read_text(reader, options) {
+        Ok(Some(text)) => FileResult::Code(language, code::count_code(&text, language)),
+        Ok(None) => FileResult::Skipped,
+        Err(e) => FileResult::Failed(e),
+    }
+}
+
+// Function to read the prose in a file for --stats and --readability, with the Markdown taken out if asked to
+fn read_prose(reader: impl Read, options: &Options) -> io::Result<Option<String>> {
+    let text = read_text(reader, options)?;
+    Ok(if options.markdown { text.map(|text| readability::strip_markdown(&text)) } else { text })
+}
+
+// Function to work out the word statistics of a file for --stats, one that looks binary is skipped
+fn count_stats(vocabulary: &Vocabulary, reader: impl Read, options: &Options) -> FileResult {
+    match read_prose(reader, options) {
+        Ok(Some(text)) => FileResult::Stats(stats::word_stats(&text, vocabulary)),
+        Ok(None) => FileResult::Skipped,
+        Err(e) => FileResult::Failed(e),
+    }
+}
+
+// Function to count what --readability scores a file by, one that looks binary is skipped
+fn count_readability(reader: impl Read, options: &Options) -> FileResult {
+    match read_prose(reader, options) {
+        Ok(Some(text)) => FileResult::Readability(readability::readability(&text)),
+        Ok(None) => FileResult::Skipped,
+        Err(e) => FileResult::Failed(e),
+    }
+}
+
+// Function to walk a directory on several threads, counting each file as soon as it is found
+fn process_directory(path: &Path, options: &Options) -> io::Result<Vec<Entry>> {
+    let entries = Mutex::new(Vec::new());
+    let walk_error = Mutex::new(None);
+    let root_device = device(&fs::metadata(path)?);
+
+    let mut walk = options.counter.walk_builder(path)?;
+    if options.archives {
+        // --include picks the members of archives, so the archives themselves have to get past it to be opened
+        walk.overrides(options.counter.overrides_with(path, &archive::globs())?);
+    }
+    walk.threads(options.jobs).build_parallel().run(|| {
+        Box::new(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                // a problem with one entry, like a symlink loop or a directory we can't read, is reported
+                // against that entry and the walk carries on, anything else stops the walk
+                Err(e) => match walk_error_entry(path, &e) {
+                    Some(entry) => {
+                        entries.lock().unwrap().push(entry);
+                        return WalkState::Continue;
+                    }
+                    None => {
+                        *walk_error.lock().unwrap() = Some(io::Error::other(e));
+                        return WalkState::Quit;
+                    }
+                },
+            };
+
+            let depth = entry.depth();
+            let metadata = entry.metadata().ok();
+            let size = metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(0);
+            let mut state = WalkState::Continue;
+            let result = if entry.path_is_symlink() && !options.counter.follow_symlinks {
+                Some(FileResult::NotWalked("symbolic link, use --follow-symlinks to count what it points to".to_string()))
+            } else if entry.file_type().is_some_and(|file_type| file_type.is_dir()) {
+                // a directory we won't go into is reported in place of its contents
+                state = WalkState::Skip;
+                if options.counter.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                    Some(FileResult::NotWalked("deeper than --max-depth".to_string()))
+                } else if options.one_file_system && depth > 0 && metadata.as_ref().and_then(device) != root_device {
+                    Some(FileResult::NotWalked("on a different file system".to_string()))
+                } else {
+                    state = WalkState::Continue;
+                    None
+                }
+            } else if options.archives && archive::is_archive(entry.path()) {
+                // an archive's members take its place, the same as the files in a directory would
+                entries.lock().unwrap().extend(archive::archive_entries(entry.path(), depth, options));
+                return state;
+            } else if entry.path().is_file() {
+                let result = match &options.cache {
+                    Some(cache) => cache.count(entry.path(), options),
+                    None => count_path(entry.path(), options),
+                };
+                // --fail-fast doesn't need to walk any further once something fails
+                if options.fail_fast && matches!(result, FileResult::Failed(_)) {
+                    state = WalkState::Quit;
+                }
+                Some(result)
+            } else {
+                Some(FileResult::NotWalked("not a regular file".to_string()))
+            };
+            entries.lock().unwrap().push(Entry { path: entry.into_path(), depth, size, is_stdin: false, result });
+            state
+        })
+    });
+
+    if let Some(e) = walk_error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    // the threads finish in any order, sorting keeps the output the same from one run to the next
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort_by(report_order);
+    Ok(entries)
+}
+
+// Function to order what a walk found the way it's reported: every directory comes right before its contents,
+// and inside a directory its own files come before its subdirectories so they're listed under its header
+// rather than after the last subdirectory's. Names are compared a folder at a time
+fn report_order(a: &Entry, b: &Entry) -> Ordering {
+    let (mut a_parts, mut b_parts) = (a.path.components(), b.path.components());
+    loop {
+        match (a_parts.next(), b_parts.next()) {
+            (Some(a_part), Some(b_part)) if a_part == b_part => {}
+            (Some(a_part), Some(b_part)) => {
+                // only a path that ends here can be a file, everything else is a folder further up it
+                let a_file = a_parts.next().is_none() && a.result.is_some();
+                let b_file = b_parts.next().is_none() && b.result.is_some();
+                return b_file.cmp(&a_file).then_with(|| a_part.cmp(&b_part));
+            }
+            // a directory comes before everything in it
+            (a_part, b_part) => return a_part.is_some().cmp(&b_part.is_some()),
+        }
+    }
+}
+
+// Function to tell whether a file or directory under the root gets counted when it isn't found by the directory
+// walk, like a file in a git revision or in an archive, going by the same --hidden, --include and --exclude rules
+// the walk uses. Every directory on the way down is checked too since the walk would never have gone into one they
+// rule out. .gitignore doesn't come into it, it only covers files on disk
+fn wanted(root: &Path, path: &Path, is_dir: bool, overrides: &Override, options: &Options) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let parts = relative.components().count();
+    let mut current = root.to_path_buf();
+    for (i, part) in relative.components().enumerate() {
+        current.push(part);
+        let hidden = part.as_os_str().to_string_lossy().starts_with('.');
+        if (hidden && !options.counter.hidden) || overrides.matched(&current, is_dir || i + 1 < parts).is_ignore() {
+            return false;
+        }
+    }
+    true
+}
+
+// Function to turn an error from the walk into an entry for the path it happened on,
+// None for errors that aren't about any one path
+fn walk_error_entry(root: &Path, error: &ignore::Error) -> Option<Entry> {
+    let (path, result) = match error {
+        ignore::Error::WithDepth { err, .. } => return walk_error_entry(root, err),
+        ignore::Error::Loop { ancestor, child } => {
+            (child.clone(), FileResult::NotWalked(format!("symbolic link loops back to \"{}\"", ancestor.display())))
+        }
+        ignore::Error::WithPath { path, err } => (path.clone(), FileResult::Failed(io::Error::other(err.to_string()))),
+        _ => return None,
+    };
+    let depth = path.strip_prefix(root).map(|relative| relative.components().count()).unwrap_or(0);
+    Some(Entry { path, depth, size: 0, is_stdin: false, result: Some(result) })
+}
+
+// Function to get the file system a file is on, so --one-file-system can tell when a directory is on another one
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+// Other platforms don't tell us, so --one-file-system has nothing to go on there
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+// A directory being reported on, along with the counts of everything seen in it so far
+struct OpenDirectory {
+    path: PathBuf,
+    totals: Totals,
+    announced: bool,
+    // whether the walk found anything in it at all, a directory whose files were all left out isn't empty
+    has_entries: bool,
+}
+
+// Function to report everything that was counted, returning the counts summed over all of it
+fn report_entries(entries: Vec<Entry>, options: &Options, output: &mut Output) -> Totals {
+    let show_rows = options.total != TotalMode::Only;
+    let mut open_directories: Vec<OpenDirectory> = Vec::new();
+    let mut root_totals = Totals::default();
+
+    for entry in entries {
+        // entries are in depth first order, so anything shallower than the open directories means we've left them
+        while open_directories.len() > entry.depth {
+            root_totals = leave_directory(&mut open_directories, options, output);
+        }
+
+        if let Some(parent) = open_directories.last_mut() {
+            parent.has_entries = true;
+        }
+        let Some(result) = entry.result else {
+            open_directories.push(OpenDirectory { path: entry.path, totals: Totals::default(), announced: false, has_entries: false });
+            continue;
+        };
+        // --code results are reported by report_code instead, and files that were left out have no row
+        if matches!(result, FileResult::Skipped | FileResult::Code(..) | FileResult::Stats(_) | FileResult::Readability(_)) {
+            continue;
+        }
+        // the directories around a file get their headers once we know it has a row
+        if show_rows {
+            announce_directories(&mut open_directories, output);
+        }
+
+        let totals = |counts| Totals { counts, files: 1, size: entry.size };
+        let file_totals = match result {
+            FileResult::Text(counts) => {
+                if show_rows {
+                    output.file(&entry.path, &totals(counts), options);
+                }
+                totals(counts)
+            }
+            FileResult::Binary(counts) => {
+                if show_rows {
+                    output.binary_file(&entry.path, &totals(counts), options);
+                }
+                totals(counts)
+            }
+            FileResult::Skipped | FileResult::Code(..) | FileResult::Stats(_) | FileResult::Readability(_) => continue,
+            FileResult::Failed(e) => {
+                report_error(&entry.path, &e);
+                output.file_error(&entry.path, entry.size, &e);
+                continue;
             }
+            FileResult::NotWalked(reason) => {
+                output.not_walked(&entry.path, &reason);
+                continue;
+            }
+        };
+
+        match open_directories.last_mut() {
+            Some(parent) => parent.totals = parent.totals + file_totals,
+            // a file given on its own has no directory around it
+            None => root_totals = root_totals + file_totals,
+        }
+    }
+
+    while !open_directories.is_empty() {
+        root_totals = leave_directory(&mut open_directories, options, output);
+    }
+    root_totals
+}
+
+// Function to print the header of every open directory that hasn't had one yet, outermost first
+fn announce_directories(open_directories: &mut [OpenDirectory], output: &mut Output) {
+    for directory in open_directories.iter_mut().filter(|directory| !directory.announced) {
+        output.directory(&directory.path);
+        directory.announced = true;
+    }
+}
+
+// Function to close the innermost open directory, adding its counts to its parent and returning them
+fn leave_directory(open_directories: &mut Vec<OpenDirectory>, options: &Options, output: &mut Output) -> Totals {
+    let mut directory = open_directories.pop().unwrap();
+    // a directory with nothing in it says so, one whose files were all left out isn't shown at all. The walk
+    // never hands over what --include, --exclude or .gitignore leave out, so it has to be looked at on disk
+    let is_empty = !directory.has_entries && fs::read_dir(&directory.path).is_ok_and(|mut listing| listing.next().is_none());
+    if !directory.announced && is_empty && options.total != TotalMode::Only {
+        announce_directories(open_directories, output);
+        output.empty_directory(&directory.path);
+        directory.announced = true;
+    }
+    if directory.announced {
+        output.leave_directory(&directory.path, &directory.totals);
+    }
+
+    if let Some(parent) = open_directories.last_mut() {
+        // the subtotal follows the whole subtree, auto skips it when it would just repeat a single file's row
+        let show_subtotal = match options.total {
+            TotalMode::Auto => directory.totals.files > 1,
+            TotalMode::Always => true,
+            TotalMode::Never | TotalMode::Only => false,
         };
+        if show_subtotal && directory.announced {
+            output.subtotal(&directory.path, &directory.totals, options);
+        }
+        parent.totals = parent.totals + directory.totals;
+    }
+    directory.totals
+}
 
-        if count_lines {
-            line_count += 1;
+// Function to gather what one of the summary reports needs from every file, the ones that failed or weren't walked
+// are reported on stderr on the way and pick leaves out anything else the report has no row for
+fn collect_files<T>(inputs: Vec<(PathBuf, Vec<Entry>)>, mut pick: impl FnMut(Entry) -> Option<T>) -> Vec<T> {
+    let mut files = Vec::new();
+    for entry in inputs.into_iter().flat_map(|(_, entries)| entries) {
+        match &entry.result {
+            Some(FileResult::Failed(e)) => report_error(&entry.path, e),
+            Some(FileResult::NotWalked(reason)) => eprintln!("Skipped \"{}\": {}", entry.path.display(), reason),
+            _ => files.extend(pick(entry)),
         }
-        if count_chars {
-            char_count += line.chars().count();
+    }
+    files
+}
+
+// Function to report what --code counted, every source file along with the totals for each language
+fn report_code(inputs: Vec<(PathBuf, Vec<Entry>)>, format: Format, options: &Options) -> io::Result<()> {
+    let files = collect_files(inputs, |entry| match entry.result {
+        Some(FileResult::Code(language, counts)) => {
+            Some(CodeFile { path: entry.path.display().to_string(), language: language.name, counts })
         }
-        if count_words {
-            word_count += line.split_whitespace().count();
+        _ => None,
+    });
+    output::write_code(&files, format, options)
+}
+
+// Function to report what --stats worked out for every file, along with the statistics across all of them
+fn report_stats(inputs: Vec<(PathBuf, Vec<Entry>)>, format: Format, options: &Options) -> io::Result<()> {
+    let files = collect_files(inputs, |entry| match entry.result {
+        Some(FileResult::Stats(stats)) => Some(StatsFile { path: entry.path.display().to_string(), stats }),
+        _ => None,
+    });
+    output::write_stats(&files, format, options)
+}
+
+// Function to report how readable --readability found every file, and all of them together
+fn report_readability(inputs: Vec<(PathBuf, Vec<Entry>)>, format: Format, options: &Options) -> io::Result<()> {
+    let files = collect_files(inputs, |entry| match entry.result {
+        Some(FileResult::Readability(counts)) => Some(ProseFile { path: entry.path.display().to_string(), counts }),
+        _ => None,
+    });
+    output::write_readability(&files, format, options)
+}
+
+// Function to sum up the counts of every file by extension, directory or language and report them
+fn report_groups(inputs: Vec<(PathBuf, Vec<Entry>)>, group_by: GroupBy, format: Format, options: &Options) -> io::Result<()> {
+    let files = collect_files(inputs, |entry| match entry.result {
+        Some(FileResult::Text(counts)) | Some(FileResult::Binary(counts)) => {
+            Some((entry.path, Totals { counts, files: 1, size: entry.size }))
+        }
+        _ => None,
+    });
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut total = Totals::default();
+    for (path, totals) in files {
+        total = total + totals;
+        let name = group_name(&path, group_by);
+        match groups.iter_mut().find(|group| group.name == name) {
+            Some(group) => group.totals = group.totals + totals,
+            None => groups.push(Group { name, totals }),
         }
     }
 
-    print!("File name:\"{}\"", path.file_name().unwrap().to_string_lossy());
-    if count_chars {
-        print!(", char count:{}", char_count);
+    // ties on a number fall back to the name so the order is always the same
+    let sort = options.sort.unwrap_or(SortKey::Name);
+    groups.sort_by(|a, b| sort_value(&b.totals, sort).cmp(&sort_value(&a.totals, sort)).then_with(|| a.name.cmp(&b.name)));
+    // the total and the percentages still cover every group, not just the ones shown
+    if let Some(top) = options.top {
+        groups.truncate(top);
     }
-    if count_words {
-        print!(", word count:{}", word_count);
+
+    let key = match group_by {
+        GroupBy::Extension => "extension",
+        GroupBy::Directory => "directory",
+        GroupBy::Language => "language",
+    };
+    output::write_groups(&groups, &total, key, format, options)
+}
+
+// Function to work out which group a file belongs in
+fn group_name(path: &Path, group_by: GroupBy) -> String {
+    match group_by {
+        GroupBy::Extension => match path.extension() {
+            Some(extension) => format!(".{}", extension.to_string_lossy()),
+            None => "(none)".to_string(),
+        },
+        GroupBy::Directory => match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+            _ => ".".to_string(),
+        },
+        GroupBy::Language => code::language_for(path).map_or("Other", |language| language.name).to_string(),
     }
-    if count_lines {
-        print!(", line count:{}", line_count);
+}
+
+// Function to get the number a sort key orders by, sorting by name compares the names instead so this is 0
+fn sort_value(totals: &Totals, sort: SortKey) -> u64 {
+    let counts = &totals.counts;
+    match sort {
+        SortKey::Name => 0,
+        SortKey::Files => totals.files as u64,
+        SortKey::Size => totals.size,
+        SortKey::Lines => counts.lines as u64,
+        SortKey::Words => counts.words as u64,
+        SortKey::Chars => counts.chars as u64,
+        SortKey::Graphemes => counts.graphemes as u64,
+        SortKey::Bytes => counts.bytes as u64,
+        SortKey::MaxLineLength => counts.max_line_length as u64,
     }
-    println!(",");
-    Ok(())
 }
 
-// Function to process a directory
-fn process_directory(path: &Path, count_chars: bool, count_words: bool, count_lines: bool) -> io::Result<()> {
-    let mut is_empty = true;
-    let mut has_subdirectories = false;
+// Function to check a file against the --min and --max filters, directories and errors always pass
+fn passes_filters(entry: &Entry, options: &Options) -> bool {
+    let (lines, bytes) = match &entry.result {
+        Some(FileResult::Text(counts)) | Some(FileResult::Binary(counts)) => (counts.lines, counts.bytes),
+        Some(FileResult::Code(_, counts)) => (counts.lines, entry.size as usize),
+        _ => return true,
+    };
+    options.min_lines.is_none_or(|min| lines >= min)
+        && options.max_lines.is_none_or(|max| lines <= max)
+        && options.min_bytes.is_none_or(|min| bytes >= min)
+        && options.max_bytes.is_none_or(|max| bytes <= max)
+}
 
-    for entry in WalkDir::new(path).min_depth(1).max_depth(1) {
-        let entry = entry?;
-        let entry_path = entry.path();
-        if entry_path.is_file() {
-            if is_empty {
-                println!("\nDirectory name: \"{}\"", path.display());
-                is_empty = false;
+// Function to put every file that was counted into --sort order and keep the --top ones, each along with
+// the path it was found under. Files that couldn't be read or were skipped come first so they aren't lost
+// off the end of the list
+fn rank_entries(inputs: Vec<(PathBuf, Vec<Entry>)>, options: &Options) -> Vec<(PathBuf, Entry)> {
+    let sort = options.sort.unwrap_or(SortKey::Name);
+    let mut failed = Vec::new();
+    let mut counted = Vec::new();
+    for (root, entries) in inputs {
+        for mut entry in entries {
+            // every file is listed on its own, outside of the directory it was found in
+            entry.depth = 0;
+            match &entry.result {
+                Some(FileResult::Text(counts)) | Some(FileResult::Binary(counts)) => {
+                    let totals = Totals { counts: *counts, files: 1, size: entry.size };
+                    counted.push((sort_value(&totals, sort), root.clone(), entry));
+                }
+                Some(FileResult::Failed(_)) | Some(FileResult::NotWalked(_)) => failed.push((root.clone(), entry)),
+                _ => {}
             }
-            process_file(entry_path, count_chars, count_words, count_lines)?;
-        } else if entry_path.is_dir() {
-            has_subdirectories = true;
-            process_directory(entry_path, count_chars, count_words, count_lines)?;
         }
     }
 
-    if is_empty && !has_subdirectories {
-        println!("\nDirectory \"{}\" is empty", path.display());
+    counted.sort_by(|(a_value, _, a), (b_value, _, b)| b_value.cmp(a_value).then_with(|| a.path.cmp(&b.path)));
+    if let Some(top) = options.top {
+        counted.truncate(top);
+    }
+    failed.extend(counted.into_iter().map(|(_, root, entry)| (root, entry)));
+    failed
+}
+
+// Function to add up the counts of every file that was counted
+fn file_totals<'a>(entries: impl Iterator<Item = &'a Entry>) -> Totals {
+    entries
+        .filter_map(|entry| match &entry.result {
+            Some(FileResult::Text(counts) | FileResult::Binary(counts)) => Some(Totals { counts: *counts, files: 1, size: entry.size }),
+            _ => None,
+        })
+        .fold(Totals::default(), Totals::add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Options the way count_lwc runs with no flags, except that nothing is cached
+    pub(crate) fn options() -> Options {
+        Options {
+            columns: vec![Field::Lines, Field::Words, Field::Bytes],
+            counter: Counter::default(),
+            width: 1,
+            total: TotalMode::Auto,
+            binary: BinaryMode::CountBytes,
+            jobs: 1,
+            code: false,
+            group_by: None,
+            sort: None,
+            top: None,
+            min_lines: None,
+            max_lines: None,
+            min_bytes: None,
+            max_bytes: None,
+            one_file_system: false,
+            fail_fast: false,
+            decompress: false,
+            archives: false,
+            cache: None,
+            rev: None,
+            stats: None,
+            top_words: 10,
+            readability: false,
+            markdown: false,
+        }
+    }
+
+    // A new empty directory for a test to make files in, named after the test so tests running at once don't share one
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("count_lwc-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // The paths of entries relative to the directory they were found in
+    pub(crate) fn relative_paths(dir: &Path, entries: &[Entry]) -> Vec<String> {
+        entries.iter().map(|entry| entry.path.strip_prefix(dir).unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    fn file(path: &str, lines: usize) -> Entry {
+        let counts = Counts { lines, bytes: lines * 10, ..Counts::default() };
+        Entry { path: PathBuf::from(path), depth: path.split('/').count(), size: counts.bytes as u64, is_stdin: false, result: Some(FileResult::Text(counts)) }
+    }
+
+    fn directory(path: &str) -> Entry {
+        Entry { path: PathBuf::from(path), depth: path.split('/').count() - 1, size: 0, is_stdin: false, result: None }
+    }
+
+    fn paths(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.path.to_str().unwrap()).collect()
     }
-    Ok(())
-}
\ No newline at end of file
+
+    #[test]
+    fn files_are_reported_before_subdirectories() {
+        let mut entries = vec![
+            file("a/z/x.rs", 1),
+            directory("a/c"),
+            file("a/y.rs", 1),
+            directory("a"),
+            file("a/c/d.rs", 1),
+            directory("a/z"),
+            file("a/b.rs", 1),
+        ];
+        entries.sort_by(report_order);
+        assert_eq!(paths(&entries), ["a", "a/b.rs", "a/y.rs", "a/c", "a/c/d.rs", "a/z", "a/z/x.rs"]);
+    }
+
+    #[test]
+    fn a_directory_that_was_not_walked_sorts_like_a_file() {
+        let mut skipped = directory("a/b");
+        skipped.result = Some(FileResult::NotWalked("deeper than --max-depth".to_string()));
+        let mut entries = vec![file("a/c/d.rs", 1), directory("a/c"), skipped, file("a/e.rs", 1), directory("a")];
+        entries.sort_by(report_order);
+        assert_eq!(paths(&entries), ["a", "a/b", "a/e.rs", "a/c", "a/c/d.rs"]);
+    }
+
+    #[test]
+    fn ranking_keeps_the_top_files_and_every_failure() {
+        let mut options = options();
+        options.sort = Some(SortKey::Lines);
+        options.top = Some(2);
+        let mut failed = file("one/broken.rs", 0);
+        failed.result = Some(FileResult::Failed(io::Error::other("unreadable")));
+        let inputs = vec![
+            (PathBuf::from("one"), vec![directory("one"), file("one/a.rs", 5), failed, file("one/b.rs", 20)]),
+            (PathBuf::from("two"), vec![directory("two"), file("two/c.rs", 20), file("two/d.rs", 1)]),
+        ];
+
+        let ranked = rank_entries(inputs, &options);
+        let ranked: Vec<(&str, &str, usize)> =
+            ranked.iter().map(|(root, entry)| (root.to_str().unwrap(), entry.path.to_str().unwrap(), entry.depth)).collect();
+        // ties go by path
+        assert_eq!(ranked, [("one", "one/broken.rs", 0), ("one", "one/b.rs", 0), ("two", "two/c.rs", 0)]);
+    }
+
+    #[test]
+    fn ranking_by_name_keeps_the_walk_order() {
+        let mut options = options();
+        options.top = Some(3);
+        let inputs = vec![(PathBuf::from("a"), vec![directory("a"), file("a/z.rs", 9), file("a/b.rs", 1), file("a/c/d.rs", 5)])];
+        let ranked: Vec<Entry> = rank_entries(inputs, &options).into_iter().map(|(_, entry)| entry).collect();
+        assert_eq!(paths(&ranked), ["a/b.rs", "a/c/d.rs", "a/z.rs"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn files0_from_keeps_names_that_are_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = temp_dir("files0-from");
+        let list = dir.join("list");
+        fs::write(&list, b"a.txt\0caf\xe9\0\0").unwrap();
+        let paths = read_files0_from(list.to_str().unwrap()).unwrap();
+        assert_eq!(paths.iter().map(|path| path.as_os_str().as_bytes()).collect::<Vec<_>>(), [&b"a.txt"[..], b"caf\xe9"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sort_and_top_do_not_go_with_code() {
+        assert!(Args::try_parse_from(["count_lwc", "--code", "--sort", "lines"]).is_err());
+        assert!(Args::try_parse_from(["count_lwc", "--code", "--top", "1"]).is_err());
+        assert!(Args::try_parse_from(["count_lwc", "--top", "1", "--sort", "words"]).is_ok());
+    }
+}
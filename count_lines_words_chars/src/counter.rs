@@ -0,0 +1,384 @@
+use encoding_rs::WINDOWS_1252;
+use std::io::{self, Read};
+use std::ops::Add;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+// How much we read from a file at a time
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Which counts to work out, anything left off is skipped entirely
+#[derive(Clone, Copy, Default)]
+pub struct Fields {
+    pub lines: bool,
+    pub words: bool,
+    pub chars: bool,
+    /// User-perceived characters, an emoji with a skin tone or a letter with combining accents counts once
+    pub graphemes: bool,
+    pub bytes: bool,
+    pub max_line_length: bool,
+    /// Count words by the Unicode word boundary rules (UAX #29) rather than by whitespace like wc,
+    /// which finds the words in text written without spaces and leaves out runs of punctuation
+    pub unicode_words: bool,
+}
+
+impl Fields {
+    /// Every count there is, with words split on whitespace
+    pub fn all() -> Fields {
+        Fields {
+            lines: true,
+            words: true,
+            chars: true,
+            graphemes: true,
+            bytes: true,
+            max_line_length: true,
+            unicode_words: false,
+        }
+    }
+}
+
+/// The counts gathered for a single file
+#[derive(Default, Clone, Copy)]
+pub struct Counts {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub graphemes: usize,
+    pub bytes: usize,
+    pub max_line_length: usize,
+}
+
+// Adding counts together sums everything except the longest line, which is the longest of the two
+impl Add for Counts {
+    type Output = Counts;
+
+    fn add(self, other: Counts) -> Counts {
+        Counts {
+            lines: self.lines + other.lines,
+            words: self.words + other.words,
+            chars: self.chars + other.chars,
+            graphemes: self.graphemes + other.graphemes,
+            bytes: self.bytes + other.bytes,
+            max_line_length: self.max_line_length.max(other.max_line_length),
+        }
+    }
+}
+
+// What the scan needs to remember from one buffer to the next
+#[derive(Default)]
+struct ScanState {
+    in_word: bool,
+    line_position: usize,
+    longest_line: usize,
+    // the last grapheme and word segment of the text so far, which the next buffer might carry on
+    grapheme_tail: String,
+    word_tail: String,
+}
+
+/// Count the given fields straight from the raw bytes. Bytes and lines never need
+/// the text decoded, so UTF-8 is only checked when chars, words or the longest line were asked for
+pub fn count_fields(mut reader: impl Read, fields: &Fields) -> io::Result<Counts> {
+    let mut counts = Counts::default();
+    let mut state = ScanState::default();
+    let mut buffer = vec![0; BUFFER_SIZE];
+    // bytes at the start of the buffer left over from a character split across two reads
+    let mut carried = 0;
+    let decode = fields.chars || fields.graphemes || fields.words || fields.max_line_length;
+
+    loop {
+        let read = match reader.read(&mut buffer[carried..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        let new_bytes = &buffer[carried..carried + read];
+        counts.bytes += read;
+        // wc counts newline characters, so a last line without one isn't counted
+        if fields.lines {
+            counts.lines += memchr::memchr_iter(b'\n', new_bytes).count();
+        }
+        if !decode {
+            continue;
+        }
+
+        let filled = carried + read;
+        let incomplete = incomplete_tail(&buffer[..filled]);
+        for_each_text(&buffer[..filled - incomplete], |text| scan_text(text, &mut counts, &mut state, fields));
+
+        buffer.copy_within(filled - incomplete..filled, 0);
+        carried = incomplete;
+    }
+
+    // a character still waiting for the rest of its bytes at the end of the file is broken, so its bytes are read on their own
+    for_each_text(&buffer[..carried], |text| scan_text(text, &mut counts, &mut state, fields));
+    counts.max_line_length = state.longest_line.max(state.line_position);
+    // whatever segment was held back at the very end is complete now
+    if !state.grapheme_tail.is_empty() {
+        counts.graphemes += 1;
+    }
+    if is_unicode_word(&state.word_tail) {
+        counts.words += 1;
+    }
+    Ok(counts)
+}
+
+// Function to hand some text that should be UTF-8 over a piece at a time. A file that turns out part way through
+// not to be UTF-8 after all is most likely a legacy Windows one, so any byte that isn't part of a valid
+// UTF-8 character is read as Windows-1252 rather than giving up on the whole file
+pub(crate) fn for_each_text(mut bytes: &[u8], mut f: impl FnMut(&str)) {
+    while !bytes.is_empty() {
+        let error = match std::str::from_utf8(bytes) {
+            Ok(text) => return f(text),
+            Err(error) => error,
+        };
+        let (valid, rest) = bytes.split_at(error.valid_up_to());
+        f(std::str::from_utf8(valid).unwrap_or_default());
+        let invalid = error.error_len().unwrap_or(rest.len());
+        f(&WINDOWS_1252.decode_without_bom_handling(&rest[..invalid]).0);
+        bytes = &rest[invalid..];
+    }
+}
+
+// Function to find how many bytes at the end of a buffer belong to a character that continues in the next read
+fn incomplete_tail(buffer: &[u8]) -> usize {
+    // a UTF-8 character is at most 4 bytes, so only the last 3 can be the start of an unfinished one
+    for back in 1..=buffer.len().min(3) {
+        let byte = buffer[buffer.len() - back];
+        // skip over continuation bytes until we find the byte the character starts with
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let length = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if length > back { back } else { 0 };
+    }
+    0
+}
+
+// Function to add the chars, words and line widths in a piece of decoded text
+fn scan_text(text: &str, counts: &mut Counts, state: &mut ScanState, fields: &Fields) {
+    if fields.chars {
+        counts.chars += text.chars().count();
+    }
+    if fields.graphemes {
+        counts.graphemes += count_graphemes(text, &mut state.grapheme_tail);
+    }
+    if fields.words && fields.unicode_words {
+        counts.words += count_unicode_words(text, &mut state.word_tail);
+    }
+
+    // plain ASCII is by far the common case and its words can be counted a byte at a time
+    let whitespace_words = fields.words && !fields.unicode_words;
+    let is_ascii = text.is_ascii();
+    if whitespace_words && is_ascii {
+        counts.words += count_ascii_words(text.as_bytes(), &mut state.in_word);
+    }
+
+    // anything else needs the decoded characters, words and line widths share a single pass over them
+    let words_by_char = whitespace_words && !is_ascii;
+    if !words_by_char && !fields.max_line_length {
+        return;
+    }
+    for c in text.chars() {
+        if words_by_char {
+            let is_space = c.is_whitespace();
+            if !is_space && !state.in_word {
+                counts.words += 1;
+            }
+            state.in_word = !is_space;
+        }
+
+        // the display width of each line, expanding tabs to 8 columns like wc -L
+        if fields.max_line_length {
+            match c {
+                // carriage returns and form feeds start a new line as far as the display is concerned
+                '\n' | '\r' | '\x0c' => {
+                    state.longest_line = state.longest_line.max(state.line_position);
+                    state.line_position = 0;
+                }
+                '\t' => state.line_position += 8 - state.line_position % 8,
+                c if c.is_control() => {}
+                c => state.line_position += c.width().unwrap_or(0),
+            }
+        }
+    }
+}
+
+// Function to count the grapheme clusters in a piece of text. A cluster can carry on into the next
+// buffer, so the last one is held back in the tail and counted along with what follows it
+fn count_graphemes(text: &str, tail: &mut String) -> usize {
+    tail.push_str(text);
+    let mut graphemes = tail.graphemes(true);
+    let Some(last) = graphemes.next_back() else {
+        return 0;
+    };
+    let count = graphemes.count();
+    *tail = last.to_string();
+    count
+}
+
+// Function to count the words in a piece of text by the Unicode word boundary rules,
+// holding back the last segment the same way count_graphemes does
+fn count_unicode_words(text: &str, tail: &mut String) -> usize {
+    tail.push_str(text);
+    let mut segments = tail.split_word_bounds();
+    let Some(last) = segments.next_back() else {
+        return 0;
+    };
+    let count = segments.filter(|segment| is_unicode_word(segment)).count();
+    *tail = last.to_string();
+    count
+}
+
+// Function to split some text into the words count_fields counts, for anything that needs the words themselves
+pub(crate) fn split_words(text: &str, unicode_words: bool) -> Box<dyn Iterator<Item = &str> + '_> {
+    if unicode_words {
+        Box::new(text.split_word_bounds().filter(|segment| is_unicode_word(segment)))
+    } else {
+        // split_whitespace splits on char::is_whitespace, the same spaces the word count goes by
+        Box::new(text.split_whitespace())
+    }
+}
+
+// Function to tell a word from the spaces and punctuation between words, the same test unicode_words() makes
+fn is_unicode_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+// Function to count the words starting in a run of ASCII. Every pair of neighbouring bytes is checked
+// on its own with no state carried through the loop, which lets the compiler vectorise it
+fn count_ascii_words(bytes: &[u8], in_word: &mut bool) -> usize {
+    let (Some(&first), Some(&last)) = (bytes.first(), bytes.last()) else {
+        return 0;
+    };
+
+    // a word starts at the first byte too if the previous buffer didn't end part way through one
+    let mut words = usize::from(!*in_word && !is_ascii_space(first));
+    words += bytes
+        .iter()
+        .zip(&bytes[1..])
+        .filter(|&(&before, &after)| is_ascii_space(before) && !is_ascii_space(after))
+        .count();
+    *in_word = !is_ascii_space(last);
+    words
+}
+
+// Function to match the ASCII characters char::is_whitespace treats as spaces
+fn is_ascii_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A reader that hands over one byte at a time, so every character and segment gets split across reads
+    struct OneByte<'a>(&'a [u8]);
+
+    impl Read for OneByte<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let Some((&first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buffer[0] = first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    fn count_split(bytes: &[u8], fields: &Fields) -> Counts {
+        count_fields(OneByte(bytes), fields).unwrap()
+    }
+
+    #[test]
+    fn incomplete_tail_finds_unfinished_characters() {
+        assert_eq!(incomplete_tail(b""), 0);
+        assert_eq!(incomplete_tail(b"abc"), 0);
+        assert_eq!(incomplete_tail("é".as_bytes()), 0);
+        assert_eq!(incomplete_tail("😀".as_bytes()), 0);
+        assert_eq!(incomplete_tail(b"a\xc3"), 1);
+        assert_eq!(incomplete_tail(b"a\xe2\x82"), 2);
+        assert_eq!(incomplete_tail(b"a\xf0\x9f\x98"), 3);
+    }
+
+    #[test]
+    fn incomplete_tail_leaves_stray_bytes_alone() {
+        // continuation bytes with nothing to continue are invalid, not unfinished
+        assert_eq!(incomplete_tail(b"\x80\x80\x80\x80"), 0);
+        assert_eq!(incomplete_tail(b"a\xff"), 0);
+    }
+
+    #[test]
+    fn count_graphemes_holds_back_the_last_cluster() {
+        let mut tail = String::new();
+        assert_eq!(count_graphemes("e", &mut tail), 0);
+        // the combining accent belongs to the e from the last piece
+        assert_eq!(count_graphemes("\u{301}x", &mut tail), 1);
+        assert_eq!(tail, "x");
+        assert_eq!(count_graphemes("", &mut tail), 0);
+        assert_eq!(tail, "x");
+    }
+
+    #[test]
+    fn count_graphemes_joins_a_flag_split_in_two() {
+        let mut tail = String::new();
+        assert_eq!(count_graphemes("🇫", &mut tail), 0);
+        assert_eq!(count_graphemes("🇷", &mut tail), 0);
+        assert_eq!(tail, "🇫🇷");
+    }
+
+    #[test]
+    fn count_unicode_words_joins_a_word_split_in_two() {
+        let mut tail = String::new();
+        assert_eq!(count_unicode_words("hel", &mut tail), 0);
+        assert_eq!(count_unicode_words("lo, world", &mut tail), 1);
+        assert_eq!(tail, "world");
+    }
+
+    #[test]
+    fn count_unicode_words_skips_punctuation() {
+        let mut tail = String::new();
+        assert_eq!(count_unicode_words("-- ... ", &mut tail), 0);
+        assert_eq!(count_unicode_words("a", &mut tail), 0);
+        assert_eq!(tail, "a");
+    }
+
+    #[test]
+    fn split_reads_count_the_same_as_one_read() {
+        let text = "héllo wörld 😀\n\te\u{301} 🇫🇷 日本語\nlast";
+        let mut fields = Fields::all();
+        let whole = count_fields(text.as_bytes(), &fields).unwrap();
+        let split = count_split(text.as_bytes(), &fields);
+        for counts in [whole, split] {
+            assert_eq!(counts.lines, 2);
+            assert_eq!(counts.words, 7);
+            assert_eq!(counts.chars, text.chars().count());
+            assert_eq!(counts.graphemes, text.graphemes(true).count());
+            assert_eq!(counts.bytes, text.len());
+            assert_eq!(counts.max_line_length, 19);
+        }
+
+        fields.unicode_words = true;
+        assert_eq!(count_split(text.as_bytes(), &fields).words, text.unicode_words().count());
+    }
+
+    #[test]
+    fn invalid_bytes_are_read_as_windows_1252() {
+        let counts = count_split(b"caf\xe9 au lait\n", &Fields::all());
+        assert_eq!((counts.lines, counts.words, counts.chars, counts.bytes), (1, 3, 13, 13));
+    }
+
+    #[test]
+    fn a_character_cut_off_at_the_end_is_read_byte_by_byte() {
+        // \xe2\x82 is the start of €, on its own it's â‚ in Windows-1252
+        for counts in [count_fields(&b"ab\xe2\x82"[..], &Fields::all()).unwrap(), count_split(b"ab\xe2\x82", &Fields::all())] {
+            assert_eq!((counts.chars, counts.bytes), (4, 4));
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use git2::{Delta, DiffFindOptions, DiffOptions, ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use count_lines_words_chars::Counts;
+
+use crate::output::DiffFile;
+use crate::{count_named, report_order, wanted, Entry, FileResult, Options};
+
+// The file mode git gives symbolic links, their blob holds the path they point to
+const SYMLINK_MODE: i32 = 0o120000;
+
+// Function to turn an error from git into one we can report, git's own messages say what went wrong
+// without the error class and code libgit2 adds on the end
+fn git_error(error: git2::Error) -> io::Error {
+    io::Error::other(error.message().to_string())
+}
+
+// Function to find the repository a path is in and where the path is inside it. The path doesn't have
+// to exist any more, it may only be in an older revision, so the repository is found from what does
+fn open_repository(path: &Path) -> io::Result<(Repository, PathBuf)> {
+    let absolute = std::path::absolute(path)?;
+    let existing = absolute.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(Path::new("/"));
+    let repository = Repository::discover(existing).map_err(git_error)?;
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| io::Error::other("a bare repository has no files to count"))?
+        .canonicalize()?;
+    // the part that doesn't exist can't be a symlink, so only what does needs resolving
+    let mut inside = existing
+        .canonicalize()?
+        .strip_prefix(&workdir)
+        .map_err(|_| io::Error::other("not inside the repository's working tree"))?
+        .to_path_buf();
+    for part in absolute.strip_prefix(existing).unwrap_or(Path::new("")).components() {
+        inside.push(part);
+    }
+    Ok((repository, inside))
+}
+
+// Function to look up the tree a revision like HEAD~3, a branch or a commit hash points at
+fn revision_tree<'r>(repository: &'r Repository, revision: &str) -> io::Result<Tree<'r>> {
+    let object = repository.revparse_single(revision).map_err(git_error)?;
+    object.peel_to_tree().map_err(git_error)
+}
+
+// Function to count a file or directory as it is at a revision, for --rev. The entries come out
+// the way the directory walk gives them, so they're reported the same way
+pub fn revision_entries(revision: &str, path: &Path, options: &Options) -> io::Result<Vec<Entry>> {
+    let (repository, inside) = open_repository(path)?;
+    let tree = revision_tree(&repository, revision)?;
+    let not_found = || io::Error::new(io::ErrorKind::NotFound, format!("not in revision {}", revision));
+
+    // a single file is counted on its own
+    let subtree = if inside.as_os_str().is_empty() {
+        tree
+    } else {
+        let entry = tree.get_path(&inside).map_err(|_| not_found())?;
+        if entry.kind() != Some(ObjectType::Tree) {
+            let blob = repository.find_blob(entry.id()).map_err(git_error)?;
+            let result = count_named(path, blob.content(), options);
+            return Ok(vec![Entry { path: path.to_path_buf(), depth: 0, size: blob.size() as u64, is_stdin: false, result: Some(result) }]);
+        }
+        repository.find_tree(entry.id()).map_err(git_error)?
+    };
+
+    let overrides = options.counter.overrides(path)?;
+    let mut entries = vec![Entry { path: path.to_path_buf(), depth: 0, size: 0, is_stdin: false, result: None }];
+    let mut error = None;
+    subtree
+        .walk(TreeWalkMode::PreOrder, |parent, member| {
+            let entry_path = path.join(parent).join(member.name().unwrap_or_default());
+            let depth = Path::new(parent).components().count() + 1;
+            let is_dir = member.kind() == Some(ObjectType::Tree);
+            if !wanted(path, &entry_path, is_dir, &overrides, options) {
+                return TreeWalkResult::Skip;
+            }
+
+            let mut walk = TreeWalkResult::Ok;
+            let (size, result) = match member.kind() {
+                Some(ObjectType::Tree) => {
+                    if options.counter.max_depth.is_some_and(|max_depth| depth >= max_depth) {
+                        walk = TreeWalkResult::Skip;
+                        (0, Some(FileResult::NotWalked("deeper than --max-depth".to_string())))
+                    } else {
+                        (0, None)
+                    }
+                }
+                // a submodule is a commit from another repository, which we don't have
+                Some(ObjectType::Commit) => (0, Some(FileResult::NotWalked("submodule".to_string()))),
+                _ if member.filemode() == SYMLINK_MODE => (0, Some(FileResult::NotWalked("symbolic link".to_string()))),
+                _ => match repository.find_blob(member.id()) {
+                    Ok(blob) => (blob.size() as u64, Some(count_named(&entry_path, blob.content(), options))),
+                    Err(e) => {
+                        error = Some(git_error(e));
+                        return TreeWalkResult::Abort;
+                    }
+                },
+            };
+            entries.push(Entry { path: entry_path, depth, size, is_stdin: false, result });
+            walk
+        })
+        .map_err(git_error)?;
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    // git lists a directory's entries by name with a '/' after directory names, they're
+    // put in the same order the directory walk gives
+    entries.sort_by(report_order);
+    Ok(entries)
+}
+
+// Function to count what changed in every file under a path between two revisions, for --diff. A range
+// is written the way git writes it, A..B, and a side left out stands for HEAD
+pub fn diff_files(range: &str, path: &Path, options: &Options) -> io::Result<Vec<DiffFile>> {
+    let (from, to) = range
+        .split_once("..")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("\"{}\" isn't a range like A..B", range)))?;
+    let (from, to) = (if from.is_empty() { "HEAD" } else { from }, if to.is_empty() { "HEAD" } else { to });
+
+    let (repository, inside) = open_repository(path)?;
+    let old_tree = revision_tree(&repository, from)?;
+    let new_tree = revision_tree(&repository, to)?;
+    let mut diff_options = DiffOptions::new();
+    if !inside.as_os_str().is_empty() {
+        diff_options.pathspec(inside.as_path());
+    }
+    let mut diff = repository
+        .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), Some(&mut diff_options))
+        .map_err(git_error)?;
+    // a file that was moved shows up once as a rename rather than as a deletion and an addition
+    diff.find_similar(Some(DiffFindOptions::new().renames(true))).map_err(git_error)?;
+
+    // paths come out of git relative to the top of the repository, they're shown under the path given
+    // the same way the directory walk shows them, but without the ./ in front when no path was given
+    let shown = |file_path: &Path| match file_path.strip_prefix(&inside) {
+        Ok(relative) if !relative.as_os_str().is_empty() && path == Path::new(".") => relative.to_path_buf(),
+        Ok(relative) if !relative.as_os_str().is_empty() => path.join(relative),
+        Ok(_) => path.to_path_buf(),
+        Err(_) => file_path.to_path_buf(),
+    };
+    let overrides = options.counter.overrides(path)?;
+    let count_blob = |id: Oid, file_path: &Path| -> io::Result<Option<Counts>> {
+        if id.is_zero() {
+            return Ok(None);
+        }
+        let blob = repository.find_blob(id).map_err(git_error)?;
+        match count_named(file_path, blob.content(), options) {
+            FileResult::Text(counts) | FileResult::Binary(counts) => Ok(Some(counts)),
+            FileResult::Failed(e) => Err(e),
+            _ => Ok(None),
+        }
+    };
+
+    let mut files = Vec::new();
+    for delta in diff.deltas() {
+        let status = match delta.status() {
+            Delta::Added | Delta::Copied => "added",
+            Delta::Deleted => "deleted",
+            Delta::Renamed => "renamed",
+            Delta::Modified | Delta::Typechange => "modified",
+            _ => continue,
+        };
+        let (old_file, new_file) = (delta.old_file(), delta.new_file());
+        let (Some(old_path), Some(new_path)) = (old_file.path(), new_file.path()) else {
+            continue;
+        };
+        let (old_path, new_path) = (shown(old_path), shown(new_path));
+        // a file asked for by name is counted whatever the globs say, the same as it would be without --diff
+        if new_path != path && !wanted(path, &new_path, false, &overrides, options) {
+            continue;
+        }
+        // submodules and symbolic links aren't counted in a revision either
+        let is_file = |mode: git2::FileMode| matches!(mode, git2::FileMode::Blob | git2::FileMode::BlobExecutable);
+        let before = if is_file(old_file.mode()) { count_blob(old_file.id(), &old_path)? } else { None };
+        let after = if is_file(new_file.mode()) { count_blob(new_file.id(), &new_path)? } else { None };
+        // binary files --binary=skip left out on both sides have nothing to show
+        if before.is_none() && after.is_none() {
+            continue;
+        }
+        let path = if status == "renamed" {
+            format!("{} -> {}", old_path.display(), new_path.display())
+        } else {
+            new_path.display().to_string()
+        };
+        files.push(DiffFile { path, status, before: before.unwrap_or_default(), after: after.unwrap_or_default() });
+    }
+    Ok(files)
+}
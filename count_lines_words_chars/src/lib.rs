@@ -0,0 +1,11 @@
+//! Counting lines, words, characters and bytes the way wc does, for tools that want to embed count_lwc
+pub mod code;
+mod counter;
+pub mod readability;
+pub mod stats;
+pub mod text;
+mod walk;
+
+pub use counter::{count_fields, Counts, Fields};
+pub use text::Counted;
+pub use walk::{count_reader, Counter, Files};
@@ -0,0 +1,920 @@
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use count_lines_words_chars::code::CodeCounts;
+use count_lines_words_chars::readability::Readability;
+use count_lines_words_chars::stats::WordStats;
+use count_lines_words_chars::Counts;
+
+use crate::{Options, TotalMode, Totals};
+
+// The output formats --format (and --tree) can pick from
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Plain,
+    Json,
+    Csv,
+    Tsv,
+    Table,
+    // only --tree picks this one
+    #[value(skip)]
+    Tree,
+}
+
+// One row of structured output: a file, a directory subtotal or the grand total
+struct Record {
+    kind: &'static str,
+    path: String,
+    totals: Totals,
+    error: Option<String>,
+}
+
+// One node of the --tree view, directories get their totals once everything under them is counted
+struct TreeNode {
+    depth: usize,
+    name: String,
+    totals: Option<Totals>,
+    // shown after the node in place of counts, like an error
+    note: Option<String>,
+}
+
+// Collects everything the program reports, plain output is printed straight away like wc does
+// while the structured formats are buffered so they can be written out as a whole at the end
+pub struct Output {
+    format: Format,
+    root: PathBuf,
+    records: Vec<Record>,
+    tree: Vec<TreeNode>,
+    open_directories: Vec<usize>,
+    // the header of the directory plain output is in, printed with the first row under it so a
+    // directory with nothing but subdirectories in it doesn't get a header of its own
+    header: Option<String>,
+}
+
+impl Output {
+    pub fn new(format: Format) -> Output {
+        Output {
+            format,
+            root: PathBuf::new(),
+            records: Vec::new(),
+            tree: Vec::new(),
+            open_directories: Vec::new(),
+            header: None,
+        }
+    }
+
+    // Set the path given on the command line that the paths reported next are shown relative to
+    pub fn set_root(&mut self, root: &Path) {
+        self.root = root.to_path_buf();
+    }
+
+    // Report the start of a directory that has something in it
+    pub fn directory(&mut self, path: &Path) {
+        match self.format {
+            Format::Plain => self.header = Some(format!("\nDirectory name: \"{}\"", self.relative(path))),
+            Format::Tree => {
+                self.open_directories.push(self.tree.len());
+                self.push_node(path, None, None);
+            }
+            _ => {}
+        }
+    }
+
+    // Report a directory with nothing in it
+    pub fn empty_directory(&mut self, path: &Path) {
+        match self.format {
+            Format::Plain => {
+                self.header = None;
+                println!("\nDirectory \"{}\" is empty", self.relative(path));
+            }
+            Format::Tree => {
+                self.open_directories.push(self.tree.len());
+                self.push_node(path, None, None);
+            }
+            _ => {}
+        }
+    }
+
+    // Report that everything under a directory has been counted
+    pub fn leave_directory(&mut self, _path: &Path, totals: &Totals) {
+        if self.format == Format::Tree {
+            if let Some(index) = self.open_directories.pop() {
+                self.tree[index].totals = Some(*totals);
+            }
+        }
+    }
+
+    // Report the counts for a single file
+    pub fn file(&mut self, path: &Path, totals: &Totals, options: &Options) {
+        match self.format {
+            Format::Plain => {
+                self.print_header();
+                print_counts(totals, &self.relative(path), options);
+            }
+            Format::Tree => self.push_node(path, Some(*totals), None),
+            _ => self.records.push(Record { kind: "file", path: self.relative(path), totals: *totals, error: None }),
+        }
+    }
+
+    // Report a binary file, only its bytes were counted
+    pub fn binary_file(&mut self, path: &Path, totals: &Totals, options: &Options) {
+        match self.format {
+            Format::Plain => {
+                self.print_header();
+                print_counts(totals, &format!("{} (binary)", self.relative(path)), options);
+            }
+            Format::Tree => {
+                self.push_node(path, Some(*totals), None);
+                if let Some(node) = self.tree.last_mut() {
+                    node.name.push_str(" (binary)");
+                }
+            }
+            _ => self.records.push(Record { kind: "binary", path: self.relative(path), totals: *totals, error: None }),
+        }
+    }
+
+    // Report a file that couldn't be counted, the error has already gone to stderr so plain output leaves it out
+    // like wc does, the other formats keep it alongside the files that were counted
+    pub fn file_error(&mut self, path: &Path, size: u64, error: &io::Error) {
+        match self.format {
+            Format::Plain => {}
+            Format::Tree => self.push_node(path, None, Some(format!("error: {}", error))),
+            _ => self.records.push(Record {
+                kind: "file",
+                path: self.relative(path),
+                totals: Totals { size, ..Totals::default() },
+                error: Some(error.to_string()),
+            }),
+        }
+    }
+
+    // Report something the walk found but didn't go into, like a symlink or a directory past --max-depth. Plain
+    // output keeps to wc's rows on stdout so it says so on stderr, the same as the other ways of reporting do
+    pub fn not_walked(&mut self, path: &Path, reason: &str) {
+        match self.format {
+            Format::Plain => eprintln!("Skipped \"{}\": {}", path.display(), reason),
+            Format::Tree => self.push_node(path, None, Some(format!("skipped: {}", reason))),
+            _ => self.records.push(Record {
+                kind: "skipped",
+                path: self.relative(path),
+                totals: Totals::default(),
+                error: Some(reason.to_string()),
+            }),
+        }
+    }
+
+    // Report the counts summed over everything under a directory, the tree already shows these on each node
+    pub fn subtotal(&mut self, path: &Path, totals: &Totals, options: &Options) {
+        match self.format {
+            Format::Plain => print_counts(totals, &format!("subtotal \"{}\"", self.relative(path)), options),
+            Format::Tree => {}
+            _ => self.records.push(Record { kind: "subtotal", path: self.relative(path), totals: *totals, error: None }),
+        }
+    }
+
+    // Report the grand total, an empty label prints the bare numbers, the tree's root node already shows it
+    pub fn total(&mut self, totals: &Totals, label: &str, options: &Options) {
+        match self.format {
+            Format::Plain => print_counts(totals, label, options),
+            Format::Tree => {}
+            _ => self.records.push(Record { kind: "total", path: String::new(), totals: *totals, error: None }),
+        }
+    }
+
+    // Write out anything that was buffered
+    pub fn finish(self, options: &Options) -> io::Result<()> {
+        match self.format {
+            Format::Plain => Ok(()),
+            Format::Json => write_json(&self.records, options),
+            Format::Csv | Format::Tsv => write_delimited(&self.records, options, self.format),
+            Format::Table => {
+                write_table(&self.records, options);
+                Ok(())
+            }
+            Format::Tree => {
+                write_tree(&self.tree, options);
+                Ok(())
+            }
+        }
+    }
+
+    // Function to print the header of the directory the next row is in, if it hasn't been printed yet
+    fn print_header(&mut self) {
+        if let Some(header) = self.header.take() {
+            println!("{}", header);
+        }
+    }
+
+    // Function to show a path relative to the root argument, the root itself is shown as it was given
+    fn relative(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.display().to_string(),
+            _ => path.display().to_string(),
+        }
+    }
+
+    // Function to add a node to the tree at the depth of its path under the root
+    fn push_node(&mut self, path: &Path, totals: Option<Totals>, note: Option<String>) {
+        let depth = path.strip_prefix(&self.root).map(|relative| relative.components().count()).unwrap_or(0);
+        let name = if depth == 0 {
+            path.display().to_string()
+        } else {
+            path.file_name().unwrap_or_default().to_string_lossy().to_string()
+        };
+        self.tree.push(TreeNode { depth, name, totals, note });
+    }
+}
+
+// Function to list the requested counts in the order they're printed as (name, value) pairs
+fn selected_counts(totals: &Totals, options: &Options) -> Vec<(&'static str, usize)> {
+    options.columns.iter().map(|field| (field.name(), field.count(&totals.counts))).collect()
+}
+
+// Function to print one row of counts in column order followed by the name
+fn print_counts(totals: &Totals, name: &str, options: &Options) {
+    let columns: Vec<String> = selected_counts(totals, options)
+        .iter()
+        .map(|(_, count)| format!("{:>1$}", count, options.width))
+        .collect();
+    if name.is_empty() {
+        println!("{}", columns.join(" "));
+    } else {
+        println!("{} {}", columns.join(" "), name);
+    }
+}
+
+// Function to print how much the counts have changed between two runs, for --watch. Only the formats meant
+// for reading get the extra row, it would get in the way of anything parsing the others
+pub fn print_change(start: &Totals, now: &Totals, format: Format, options: &Options) {
+    if !matches!(format, Format::Plain | Format::Tree | Format::Table) {
+        return;
+    }
+    let columns: Vec<String> = options
+        .columns
+        .iter()
+        .map(|field| {
+            let change = field.count(&now.counts) as i64 - field.count(&start.counts) as i64;
+            format!("{:>+1$}", change, options.width)
+        })
+        .collect();
+    println!("{} since start", columns.join(" "));
+}
+
+// Function to write the records as a single JSON document with the files, subtotals and total split out
+fn write_json(records: &[Record], options: &Options) -> io::Result<()> {
+    let mut files = Vec::new();
+    let mut subtotals = Vec::new();
+    let mut skipped = Vec::new();
+    let mut total = Value::Null;
+
+    for record in records {
+        if record.kind == "skipped" {
+            skipped.push(json!({ "path": record.path, "reason": record.error }));
+            continue;
+        }
+        let mut object = Map::new();
+        if record.kind != "total" {
+            object.insert("path".to_string(), json!(record.path));
+        }
+        if record.kind == "subtotal" || record.kind == "total" {
+            object.insert("files".to_string(), json!(record.totals.files));
+        }
+        if record.kind == "binary" {
+            object.insert("binary".to_string(), json!(true));
+        }
+        object.insert("size".to_string(), json!(record.totals.size));
+        match &record.error {
+            Some(error) => {
+                object.insert("error".to_string(), json!(error));
+            }
+            None => {
+                for (name, count) in selected_counts(&record.totals, options) {
+                    object.insert(name.to_string(), json!(count));
+                }
+            }
+        }
+
+        match record.kind {
+            "file" | "binary" => files.push(Value::Object(object)),
+            "subtotal" => subtotals.push(Value::Object(object)),
+            _ => total = Value::Object(object),
+        }
+    }
+
+    let document = json!({ "files": files, "subtotals": subtotals, "skipped": skipped, "total": total });
+    println!("{}", serde_json::to_string_pretty(&document)?);
+    Ok(())
+}
+
+// Function to write the records as CSV or TSV with a header row
+fn write_delimited(records: &[Record], options: &Options, format: Format) -> io::Result<()> {
+    let mut header = vec!["type", "path", "files", "size"];
+    header.extend(selected_counts(&Totals::default(), options).iter().map(|(name, _)| *name));
+    header.push("error");
+
+    let rows = records.iter().map(|record| {
+        let mut row = vec![
+            record.kind.to_string(),
+            record.path.clone(),
+            record.totals.files.to_string(),
+            record.totals.size.to_string(),
+        ];
+        for (_, count) in selected_counts(&record.totals, options) {
+            // a file that failed has no counts, so leave those cells empty
+            row.push(if record.error.is_some() { String::new() } else { count.to_string() });
+        }
+        row.push(record.error.clone().unwrap_or_default());
+        row
+    });
+    write_csv(format, header, rows)
+}
+
+// Function to write a header and rows of cells as CSV, or as TSV for --format=tsv
+fn write_csv<H: AsRef<[u8]>>(format: Format, header: impl IntoIterator<Item = H>, rows: impl IntoIterator<Item = Vec<String>>) -> io::Result<()> {
+    let delimiter = if format == Format::Tsv { b'\t' } else { b',' };
+    let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(io::stdout());
+    writer.write_record(header)?;
+    for row in rows {
+        writer.write_record(&row)?;
+    }
+    writer.flush()
+}
+
+// Function to tell whether the total row is shown, --total works the same for every report. Auto leaves it
+// off when there's only the one row it would repeat
+pub fn show_total(options: &Options, rows: usize) -> bool {
+    match options.total {
+        TotalMode::Auto => rows > 1,
+        TotalMode::Always | TotalMode::Only => true,
+        TotalMode::Never => false,
+    }
+}
+
+// Function to write the records as a human readable table with aligned columns
+fn write_table(records: &[Record], options: &Options) {
+    let mut header = vec!["TYPE".to_string(), "PATH".to_string(), "FILES".to_string(), "SIZE".to_string()];
+    header.extend(selected_counts(&Totals::default(), options).iter().map(|(name, _)| name.to_uppercase()));
+
+    let mut rows = Vec::new();
+    for record in records {
+        let mut row = vec![
+            record.kind.to_string(),
+            record.path.clone(),
+            record.totals.files.to_string(),
+            record.totals.size.to_string(),
+        ];
+        // a file that failed has no counts, the error goes at the end of the row instead
+        if record.error.is_none() {
+            row.extend(selected_counts(&record.totals, options).iter().map(|(_, count)| count.to_string()));
+        }
+        let note = match &record.error {
+            Some(reason) if record.kind == "skipped" => Some(format!("skipped: {}", reason)),
+            Some(error) => Some(format!("error: {}", error)),
+            None => None,
+        };
+        rows.push((row, note));
+    }
+
+    // type and path are left aligned, the numbers are right aligned
+    print_table(header, rows, 2);
+}
+
+// Function to print a header and rows of cells as a table, the way the reports that need their column names show
+fn print_table<H: ToString>(header: impl IntoIterator<Item = H>, rows: impl IntoIterator<Item = (Vec<String>, Option<String>)>, text_columns: usize) {
+    let mut table = vec![(header.into_iter().map(|cell| cell.to_string()).collect(), None)];
+    table.extend(rows);
+    print_aligned(&table, text_columns);
+}
+
+// Function to print rows of cells lined up in columns, the first few columns are text and left aligned,
+// the rest are numbers and right aligned. Any note, like an error, goes after the last column
+fn print_aligned(rows: &[(Vec<String>, Option<String>)], text_columns: usize) {
+    // each column is as wide as its widest cell
+    let mut widths = vec![0; rows[0].0.len()];
+    for (row, _) in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    for (row, note) in rows {
+        let mut cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i < text_columns {
+                    format!("{:<1$}", cell, widths[i])
+                } else {
+                    format!("{:>1$}", cell, widths[i])
+                }
+            })
+            .collect();
+        if let Some(note) = note {
+            cells.push(note.clone());
+        }
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+// Function to draw the tree with box drawing branches and each node's counts lined up on the right
+fn write_tree(nodes: &[TreeNode], options: &Options) {
+    // work out the branch drawn in front of every node, a node is the last child when
+    // nothing at its own depth follows before the tree climbs back out of its parent
+    let mut labels = Vec::new();
+    let mut ancestors_last: Vec<bool> = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        let is_last = nodes[i + 1..]
+            .iter()
+            .find(|next| next.depth <= node.depth)
+            .is_none_or(|next| next.depth < node.depth);
+
+        // the root has no branch, every level between it and the node carries on its parent's line
+        let mut label = String::new();
+        ancestors_last.truncate(node.depth);
+        for &last in ancestors_last.iter().skip(1) {
+            label.push_str(if last { "    " } else { "│   " });
+        }
+        if node.depth > 0 {
+            label.push_str(if is_last { "└── " } else { "├── " });
+        }
+        ancestors_last.push(is_last);
+        label.push_str(&node.name);
+        labels.push(label);
+    }
+
+    let width = labels.iter().map(|label| label.chars().count()).max().unwrap_or(0);
+    for (label, node) in labels.iter().zip(nodes) {
+        let padding = " ".repeat(width - label.chars().count());
+        match (&node.totals, &node.note) {
+            (_, Some(note)) => println!("{}{}  {}", label, padding, note),
+            (Some(totals), None) => {
+                let columns: Vec<String> = selected_counts(totals, options)
+                    .iter()
+                    .map(|(_, count)| format!("{:>1$}", count, options.width))
+                    .collect();
+                println!("{}{}  {}", label, padding, columns.join(" "));
+            }
+            (None, None) => println!("{}", label),
+        }
+    }
+}
+
+// One source file counted by --code
+pub struct CodeFile {
+    pub path: String,
+    pub language: &'static str,
+    pub counts: CodeCounts,
+}
+
+// Function to report --code results: every file, then every language, then the total across all of them
+pub fn write_code(files: &[CodeFile], format: Format, options: &Options) -> io::Result<()> {
+    // the languages are listed in name order along with how many files each had
+    let mut languages: BTreeMap<&str, (usize, CodeCounts)> = BTreeMap::new();
+    for file in files {
+        let language = languages.entry(file.language).or_default();
+        language.0 += 1;
+        language.1 = language.1 + file.counts;
+    }
+    let total = files.iter().fold(CodeCounts::default(), |total, file| total + file.counts);
+
+    // --total works the same as it does for plain counts, auto leaves it off when there
+    // is a single language since it would repeat that language's row
+    let show_rows = options.total != TotalMode::Only;
+    let show_total = show_total(options, languages.len());
+
+    // each row is its type, path, language, file count and then the line counts
+    let row = |kind: &str, path: &str, language: &str, count: usize, counts: &CodeCounts| {
+        vec![
+            kind.to_string(),
+            path.to_string(),
+            language.to_string(),
+            count.to_string(),
+            counts.lines.to_string(),
+            counts.code.to_string(),
+            counts.comments.to_string(),
+            counts.blanks.to_string(),
+        ]
+    };
+    let mut rows = Vec::new();
+    if show_rows {
+        rows.extend(files.iter().map(|file| row("file", &file.path, file.language, 1, &file.counts)));
+        rows.extend(languages.iter().map(|(name, (count, counts))| row("language", "", name, *count, counts)));
+    }
+    if show_total {
+        rows.push(row("total", "", "", files.len(), &total));
+    }
+
+    match format {
+        Format::Json => {
+            let counts_object = |object: &mut Map<String, Value>, counts: &CodeCounts| {
+                object.insert("lines".to_string(), json!(counts.lines));
+                object.insert("code".to_string(), json!(counts.code));
+                object.insert("comments".to_string(), json!(counts.comments));
+                object.insert("blanks".to_string(), json!(counts.blanks));
+            };
+            let mut document = Map::new();
+            if show_rows {
+                let files: Vec<Value> = files
+                    .iter()
+                    .map(|file| {
+                        let mut object = Map::new();
+                        object.insert("path".to_string(), json!(file.path));
+                        object.insert("language".to_string(), json!(file.language));
+                        counts_object(&mut object, &file.counts);
+                        Value::Object(object)
+                    })
+                    .collect();
+                document.insert("files".to_string(), json!(files));
+                let languages: Vec<Value> = languages
+                    .iter()
+                    .map(|(name, (count, counts))| {
+                        let mut object = Map::new();
+                        object.insert("language".to_string(), json!(name));
+                        object.insert("files".to_string(), json!(count));
+                        counts_object(&mut object, counts);
+                        Value::Object(object)
+                    })
+                    .collect();
+                document.insert("languages".to_string(), json!(languages));
+            }
+            let mut object = Map::new();
+            object.insert("files".to_string(), json!(files.len()));
+            counts_object(&mut object, &total);
+            document.insert("total".to_string(), Value::Object(object));
+            println!("{}", serde_json::to_string_pretty(&Value::Object(document))?);
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => write_csv(format, ["type", "path", "language", "files", "lines", "code", "comments", "blanks"], rows),
+        // plain and table both come out as a table since four unlabelled numbers in a row would be hard to read
+        _ => {
+            let header = ["TYPE", "PATH", "LANGUAGE", "FILES", "LINES", "CODE", "COMMENTS", "BLANKS"];
+            print_table(header, rows.into_iter().map(|row| (row, None)), 3);
+            Ok(())
+        }
+    }
+}
+
+// The counts for one group of files in a --group-by summary
+pub struct Group {
+    pub name: String,
+    pub totals: Totals,
+}
+
+// Function to report a --group-by summary, every group with its counts and its share of the total
+pub fn write_groups(groups: &[Group], total: &Totals, key: &str, format: Format, options: &Options) -> io::Result<()> {
+    let show_rows = options.total != TotalMode::Only;
+    // the total covers the groups --top leaves out too, which auto counts as a row of their own
+    let shown_files: usize = groups.iter().map(|group| group.totals.files).sum();
+    let show_total = show_total(options, groups.len() + usize::from(shown_files < total.files));
+
+    // each column comes with its share of the total, worked out as a percentage
+    let percent = |part: u64, whole: u64| if whole == 0 { 0.0 } else { part as f64 * 100.0 / whole as f64 };
+    let columns = |totals: &Totals| {
+        let mut columns = vec![("files", totals.files as u64, percent(totals.files as u64, total.files as u64))];
+        let whole = selected_counts(total, options);
+        for ((name, count), (_, whole)) in selected_counts(totals, options).into_iter().zip(whole) {
+            columns.push((name, count as u64, percent(count as u64, whole as u64)));
+        }
+        columns
+    };
+
+    let mut rows: Vec<(&str, &str, &Totals)> = Vec::new();
+    if show_rows {
+        rows.extend(groups.iter().map(|group| ("group", group.name.as_str(), &group.totals)));
+    }
+    if show_total {
+        rows.push(("total", "", total));
+    }
+
+    match format {
+        Format::Json => {
+            let objects = |name: Option<&str>, totals: &Totals| {
+                let mut object = Map::new();
+                let mut shares = Map::new();
+                if let Some(name) = name {
+                    object.insert(key.to_string(), json!(name));
+                }
+                for (column, count, share) in columns(totals) {
+                    object.insert(column.to_string(), json!(count));
+                    shares.insert(column.to_string(), json!((share * 10.0).round() / 10.0));
+                }
+                object.insert("percent".to_string(), Value::Object(shares));
+                Value::Object(object)
+            };
+            let mut document = Map::new();
+            if show_rows {
+                let groups: Vec<Value> = groups.iter().map(|group| objects(Some(&group.name), &group.totals)).collect();
+                document.insert("groups".to_string(), json!(groups));
+            }
+            document.insert("total".to_string(), objects(None, total));
+            println!("{}", serde_json::to_string_pretty(&Value::Object(document))?);
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => {
+            let mut header = vec!["type".to_string(), key.to_string()];
+            for (column, _, _) in columns(total) {
+                header.push(column.to_string());
+                header.push(format!("{}_percent", column));
+            }
+            let rows = rows.into_iter().map(|(kind, name, totals)| {
+                let mut row = vec![kind.to_string(), name.to_string()];
+                for (_, count, share) in columns(totals) {
+                    row.push(count.to_string());
+                    row.push(format!("{:.1}", share));
+                }
+                row
+            });
+            write_csv(format, header, rows)
+        }
+        // plain and table both come out as a table, the summary needs its column names
+        _ => {
+            let mut header = vec![key.to_uppercase()];
+            for (column, _, _) in columns(total) {
+                header.push(column.to_uppercase());
+                header.push(format!("%{}", column.to_uppercase()));
+            }
+            let rows = rows.into_iter().map(|(kind, name, totals)| {
+                let mut row = vec![if kind == "total" { "total".to_string() } else { name.to_string() }];
+                for (_, count, share) in columns(totals) {
+                    row.push(count.to_string());
+                    row.push(format!("{:.1}%", share));
+                }
+                (row, None)
+            });
+            print_table(header, rows, 1);
+            Ok(())
+        }
+    }
+}
+
+// One file that changed between the two revisions --diff compares, a file that was added or
+// deleted has nothing on the side it isn't in
+pub struct DiffFile {
+    pub path: String,
+    pub status: &'static str,
+    pub before: Counts,
+    pub after: Counts,
+}
+
+// Function to report --diff results: how much every count changed in every file, then across all of them
+pub fn write_diff(files: &[DiffFile], format: Format, options: &Options) -> io::Result<()> {
+    let before = files.iter().fold(Counts::default(), |total, file| total + file.before);
+    let after = files.iter().fold(Counts::default(), |total, file| total + file.after);
+    let show_rows = options.total != TotalMode::Only;
+    let show_total = show_total(options, files.len());
+
+    // every column is its count before, after, and the difference between them
+    let columns = |before: &Counts, after: &Counts| -> Vec<(&'static str, usize, usize, i64)> {
+        options
+            .columns
+            .iter()
+            .map(|field| {
+                let (old, new) = (field.count(before), field.count(after));
+                (field.name(), old, new, new as i64 - old as i64)
+            })
+            .collect()
+    };
+    let mut rows: Vec<(&str, &str, &str, &Counts, &Counts)> = Vec::new();
+    if show_rows {
+        rows.extend(files.iter().map(|file| ("file", file.path.as_str(), file.status, &file.before, &file.after)));
+    }
+    if show_total {
+        rows.push(("total", "", "", &before, &after));
+    }
+
+    match format {
+        Format::Json => {
+            let object = |before: &Counts, after: &Counts, object: &mut Map<String, Value>| {
+                for (name, old, new, change) in columns(before, after) {
+                    object.insert(name.to_string(), json!({ "before": old, "after": new, "change": change }));
+                }
+            };
+            let mut document = Map::new();
+            if show_rows {
+                let files: Vec<Value> = files
+                    .iter()
+                    .map(|file| {
+                        let mut file_object = Map::new();
+                        file_object.insert("path".to_string(), json!(file.path));
+                        file_object.insert("status".to_string(), json!(file.status));
+                        object(&file.before, &file.after, &mut file_object);
+                        Value::Object(file_object)
+                    })
+                    .collect();
+                document.insert("files".to_string(), json!(files));
+            }
+            let mut total = Map::new();
+            total.insert("files".to_string(), json!(files.len()));
+            object(&before, &after, &mut total);
+            document.insert("total".to_string(), Value::Object(total));
+            println!("{}", serde_json::to_string_pretty(&Value::Object(document))?);
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => {
+            let mut header = vec!["type".to_string(), "path".to_string(), "status".to_string()];
+            for (name, ..) in columns(&before, &after) {
+                header.extend([format!("{}_before", name), format!("{}_after", name), format!("{}_change", name)]);
+            }
+            let rows = rows.into_iter().map(|(kind, path, status, before, after)| {
+                let mut row = vec![kind.to_string(), path.to_string(), status.to_string()];
+                for (_, old, new, change) in columns(before, after) {
+                    row.extend([old.to_string(), new.to_string(), change.to_string()]);
+                }
+                row
+            });
+            write_csv(format, header, rows)
+        }
+        Format::Table => {
+            let mut header = vec!["STATUS".to_string(), "PATH".to_string()];
+            header.extend(columns(&before, &after).iter().map(|(name, ..)| name.to_uppercase()));
+            let rows = rows.into_iter().map(|(kind, path, status, before, after)| {
+                let mut row = vec![if kind == "total" { "total".to_string() } else { status.to_string() }, path.to_string()];
+                row.extend(columns(before, after).iter().map(|(.., change)| format!("{:+}", change)));
+                (row, None)
+            });
+            print_table(header, rows, 2);
+            Ok(())
+        }
+        // plain output looks like wc's with the changes in place of the counts, the width fits the widest change
+        _ => {
+            let lines: Vec<(Vec<String>, String)> = rows
+                .into_iter()
+                .map(|(kind, path, status, before, after)| {
+                    let changes = columns(before, after).iter().map(|(.., change)| format!("{:+}", change)).collect();
+                    let name = match (kind, status) {
+                        ("total", _) if options.total == TotalMode::Only => String::new(),
+                        ("total", _) => "total".to_string(),
+                        (_, "added" | "deleted") => format!("{} ({})", path, status),
+                        _ => path.to_string(),
+                    };
+                    (changes, name)
+                })
+                .collect();
+            let width = lines.iter().flat_map(|(changes, _)| changes).map(String::len).max().unwrap_or(1);
+            for (changes, name) in lines {
+                let changes: Vec<String> = changes.iter().map(|change| format!("{:>1$}", change, width)).collect();
+                if name.is_empty() {
+                    println!("{}", changes.join(" "));
+                } else {
+                    println!("{} {}", changes.join(" "), name);
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+// The word statistics --stats worked out for one file
+pub struct StatsFile {
+    pub path: String,
+    pub stats: WordStats,
+}
+
+// Function to report --stats results: the statistics for every file, then across all of them
+pub fn write_stats(files: &[StatsFile], format: Format, options: &Options) -> io::Result<()> {
+    let total = files.iter().fold(WordStats::default(), |total, file| total + file.stats.clone());
+    let show_rows = options.total != TotalMode::Only;
+    let show_total = show_total(options, files.len());
+    let mut rows: Vec<(&str, &str, &WordStats)> = Vec::new();
+    if show_rows {
+        rows.extend(files.iter().map(|file| ("file", file.path.as_str(), &file.stats)));
+    }
+    if show_total {
+        rows.push(("total", "", &total));
+    }
+
+    // the averages are rounded to a tenth, the same as --group-by's percentages
+    let numbers = |stats: &WordStats| {
+        vec![
+            stats.words.to_string(),
+            stats.unique_words().to_string(),
+            format!("{:.1}", stats.average_word_length()),
+            format!("{:.1}", stats.words_per_line()),
+        ]
+    };
+    let top_words = |stats: &WordStats| {
+        let words: Vec<String> = stats.top_words(options.top_words).iter().map(|(word, count)| format!("{} ({})", word, count)).collect();
+        words.join(", ")
+    };
+
+    match format {
+        Format::Json => {
+            let object = |path: Option<&str>, stats: &WordStats| {
+                let mut object = Map::new();
+                if let Some(path) = path {
+                    object.insert("path".to_string(), json!(path));
+                }
+                object.insert("words".to_string(), json!(stats.words));
+                object.insert("unique_words".to_string(), json!(stats.unique_words()));
+                object.insert("average_word_length".to_string(), json!((stats.average_word_length() * 10.0).round() / 10.0));
+                object.insert("words_per_line".to_string(), json!((stats.words_per_line() * 10.0).round() / 10.0));
+                let top: Vec<Value> =
+                    stats.top_words(options.top_words).iter().map(|(word, count)| json!({ "word": word, "count": count })).collect();
+                object.insert("top_words".to_string(), json!(top));
+                Value::Object(object)
+            };
+            let mut document = Map::new();
+            if show_rows {
+                let files: Vec<Value> = files.iter().map(|file| object(Some(&file.path), &file.stats)).collect();
+                document.insert("files".to_string(), json!(files));
+            }
+            document.insert("total".to_string(), object(None, &total));
+            println!("{}", serde_json::to_string_pretty(&Value::Object(document))?);
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => {
+            let header = ["type", "path", "words", "unique_words", "average_word_length", "words_per_line", "top_words"];
+            let rows = rows.into_iter().map(|(kind, path, stats)| {
+                let mut row = vec![kind.to_string(), path.to_string()];
+                row.extend(numbers(stats));
+                row.push(top_words(stats));
+                row
+            });
+            write_csv(format, header, rows)
+        }
+        // plain and table both come out as a table, with the most frequent words after the numbers
+        _ => {
+            let header = ["TYPE", "PATH", "WORDS", "UNIQUE", "AVG LENGTH", "WORDS/LINE", "TOP WORDS"];
+            let rows = rows.into_iter().map(|(kind, path, stats)| {
+                let mut row = vec![kind.to_string(), path.to_string()];
+                row.extend(numbers(stats));
+                (row, Some(top_words(stats)))
+            });
+            print_table(header, rows, 2);
+            Ok(())
+        }
+    }
+}
+
+// What --readability counted in one file
+pub struct ProseFile {
+    pub path: String,
+    pub counts: Readability,
+}
+
+// Function to report --readability results: the counts and scores for every file, then for all of them together
+pub fn write_readability(files: &[ProseFile], format: Format, options: &Options) -> io::Result<()> {
+    let total = files.iter().fold(Readability::default(), |total, file| total + file.counts);
+    let show_rows = options.total != TotalMode::Only;
+    let show_total = show_total(options, files.len());
+    let mut rows: Vec<(&str, &str, &Readability)> = Vec::new();
+    if show_rows {
+        rows.extend(files.iter().map(|file| ("file", file.path.as_str(), &file.counts)));
+    }
+    if show_total {
+        rows.push(("total", "", &total));
+    }
+
+    // a file with no words has no scores, which is left empty rather than shown as a made up number
+    let score = |score: Option<f64>| score.map(|score| format!("{:.1}", score)).unwrap_or_default();
+    let cells = |kind: &str, path: &str, counts: &Readability| {
+        vec![
+            kind.to_string(),
+            path.to_string(),
+            counts.sentences.to_string(),
+            counts.words.to_string(),
+            counts.syllables.to_string(),
+            score(counts.reading_ease()),
+            score(counts.grade_level()),
+        ]
+    };
+
+    match format {
+        Format::Json => {
+            let object = |path: Option<&str>, counts: &Readability| {
+                let mut object = Map::new();
+                if let Some(path) = path {
+                    object.insert("path".to_string(), json!(path));
+                }
+                object.insert("sentences".to_string(), json!(counts.sentences));
+                object.insert("words".to_string(), json!(counts.words));
+                object.insert("syllables".to_string(), json!(counts.syllables));
+                let rounded = |score: Option<f64>| score.map(|score| (score * 10.0).round() / 10.0);
+                object.insert("reading_ease".to_string(), json!(rounded(counts.reading_ease())));
+                object.insert("grade_level".to_string(), json!(rounded(counts.grade_level())));
+                Value::Object(object)
+            };
+            let mut document = Map::new();
+            if show_rows {
+                let files: Vec<Value> = files.iter().map(|file| object(Some(&file.path), &file.counts)).collect();
+                document.insert("files".to_string(), json!(files));
+            }
+            document.insert("total".to_string(), object(None, &total));
+            println!("{}", serde_json::to_string_pretty(&Value::Object(document))?);
+            Ok(())
+        }
+        Format::Csv | Format::Tsv => {
+            let header = ["type", "path", "sentences", "words", "syllables", "reading_ease", "grade_level"];
+            write_csv(format, header, rows.into_iter().map(|(kind, path, counts)| cells(kind, path, counts)))
+        }
+        // plain and table both come out as a table, the scores need their column names
+        _ => {
+            let header = ["TYPE", "PATH", "SENTENCES", "WORDS", "SYLLABLES", "READING EASE", "GRADE"];
+            print_table(header, rows.into_iter().map(|(kind, path, counts)| (cells(kind, path, counts), None)), 2);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use std::ops::Add;
+
+use crate::counter::split_words;
+
+// Abbreviations that end in a full stop without ending the sentence
+const ABBREVIATIONS: &[&str] = &["e.g.", "i.e.", "cf.", "vs.", "mr.", "mrs.", "ms.", "dr.", "prof.", "st.", "no.", "fig."];
+
+/// What the Flesch readability scores are worked out from
+#[derive(Default, Clone, Copy)]
+pub struct Readability {
+    pub sentences: usize,
+    pub words: usize,
+    pub syllables: usize,
+}
+
+impl Add for Readability {
+    type Output = Readability;
+
+    fn add(self, other: Readability) -> Readability {
+        Readability {
+            sentences: self.sentences + other.sentences,
+            words: self.words + other.words,
+            syllables: self.syllables + other.syllables,
+        }
+    }
+}
+
+impl Readability {
+    /// The Flesch reading ease, from around 100 for text a child can read down to 0 and below for
+    /// academic writing. None for text without any words to score
+    pub fn reading_ease(&self) -> Option<f64> {
+        let (words_per_sentence, syllables_per_word) = self.ratios()?;
+        Some(206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word)
+    }
+
+    /// The Flesch-Kincaid grade level, roughly the US school grade needed to follow the text
+    pub fn grade_level(&self) -> Option<f64> {
+        let (words_per_sentence, syllables_per_word) = self.ratios()?;
+        Some(0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59)
+    }
+
+    // Function to work out the average sentence length in words and word length in syllables both scores use
+    fn ratios(&self) -> Option<(f64, f64)> {
+        if self.words == 0 || self.sentences == 0 {
+            return None;
+        }
+        Some((self.words as f64 / self.sentences as f64, self.syllables as f64 / self.words as f64))
+    }
+}
+
+/// Count the sentences, words and syllables in some prose. A sentence ends at a full stop,
+/// question mark or exclamation mark at the end of a word, or at a blank line for headings and list items
+/// that don't have one. Only words with letters in them count, a number or a symbol has no syllables to speak of
+pub fn readability(text: &str) -> Readability {
+    let mut counts = Readability::default();
+    let mut in_sentence = false;
+    for line in text.lines() {
+        if line.trim().is_empty() && in_sentence {
+            counts.sentences += 1;
+            in_sentence = false;
+        }
+        for word in split_words(line, false) {
+            let letters = word.trim_matches(|c: char| !c.is_alphanumeric());
+            if letters.chars().any(char::is_alphabetic) {
+                counts.words += 1;
+                counts.syllables += syllables(letters);
+                in_sentence = true;
+            }
+
+            // closing quotes and brackets can come after the full stop
+            let end = word.trim_end_matches(['"', '\'', ')', ']', '”', '’']);
+            let is_abbreviation = ABBREVIATIONS.contains(&end.to_lowercase().as_str())
+                // a single capital letter with a full stop is an initial, like the J. in J. R. R. Tolkien
+                || (end.len() == 2 && end.starts_with(|c: char| c.is_ascii_uppercase()) && end.ends_with('.'));
+            if in_sentence && end.ends_with(['.', '!', '?']) && !is_abbreviation {
+                counts.sentences += 1;
+                in_sentence = false;
+            }
+        }
+    }
+    if in_sentence {
+        counts.sentences += 1;
+    }
+    counts
+}
+
+/// Estimate the syllables in an English word by counting its groups of vowels, the same rule
+/// of thumb most readability tools use. A silent e on the end doesn't count, but every word has at least one
+pub fn syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for c in word.chars() {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    // "make" has one syllable but "table" has two
+    let chars: Vec<char> = word.chars().collect();
+    if let [.., before, last, 'e'] = chars[..] {
+        let consonant_le = last == 'l' && !is_vowel(before);
+        if !is_vowel(last) && !consonant_le {
+            count -= 1;
+        }
+    }
+    count.max(1)
+}
+
+/// Strip Markdown down to the prose in it so code and link targets don't skew the scores or
+/// the word statistics. Code blocks and inline code go, links keep their text but lose their URL, and
+/// headings and list items are set apart as paragraphs of their own since they don't end in a full stop
+pub fn strip_markdown(text: &str) -> String {
+    let mut prose = String::with_capacity(text.len());
+    // the fence a fenced code block was opened with, it only ends at the same kind of fence
+    let mut fence: Option<&str> = None;
+    let mut previous_blank = true;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some(open) = ["```", "~~~"].into_iter().find(|marker| trimmed.starts_with(marker)) {
+            fence = Some(open);
+            continue;
+        }
+        // an indented code block has to follow a blank line, otherwise it's a paragraph carrying on
+        let indented = line.starts_with("    ") || line.starts_with('\t');
+        if indented && previous_blank && !trimmed.is_empty() {
+            continue;
+        }
+        previous_blank = trimmed.is_empty();
+        // link reference definitions, like [1]: https://example.com, are nothing but a URL
+        if trimmed.starts_with('[') && trimmed.contains("]:") {
+            continue;
+        }
+
+        let heading = trimmed.starts_with('#') && trimmed.trim_start_matches('#').starts_with(' ');
+        let numbered = trimmed.trim_start_matches(|c: char| c.is_ascii_digit());
+        let list_item = ["- ", "* ", "+ "].iter().any(|marker| trimmed.starts_with(marker))
+            || (numbered.len() < trimmed.len() && (numbered.starts_with(". ") || numbered.starts_with(") ")));
+        let content = if heading {
+            trimmed.trim_start_matches('#')
+        } else if list_item {
+            trimmed.split_once(' ').map_or("", |(_, item)| item)
+        } else {
+            trimmed
+        };
+        if heading || list_item {
+            prose.push('\n');
+        }
+        prose.push_str(&strip_inline(content.trim_start()));
+        prose.push('\n');
+        if heading || list_item {
+            prose.push('\n');
+        }
+    }
+    prose
+}
+
+// Function to strip the Markdown inside a line: inline code, link URLs, bare URLs and HTML tags
+fn strip_inline(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(c) = rest.chars().next() {
+        // `code` goes along with the backticks, however many of them there are
+        if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let after = &rest[ticks..];
+            match after.find(&rest[..ticks]) {
+                Some(end) => rest = &after[end + ticks..],
+                None => rest = after,
+            }
+            continue;
+        }
+        // [text](url) and ![alt](url) keep their text
+        if c == ']' && rest[1..].starts_with('(') {
+            rest = match rest.find(')') {
+                Some(end) => &rest[end + 1..],
+                None => &rest[1..],
+            };
+            continue;
+        }
+        // <https://example.com> and HTML tags
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        if rest.starts_with("http://") || rest.starts_with("https://") {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = &rest[end..];
+            continue;
+        }
+        // the brackets, the ! in front of an image and emphasis markers aren't part of the words
+        let image = c == '!' && rest[1..].starts_with('[');
+        if !image && !matches!(c, '[' | '*' | '_') {
+            text.push(c);
+        }
+        rest = &rest[c.len_utf8()..];
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syllables_are_groups_of_vowels() {
+        assert_eq!(syllables("cat"), 1);
+        assert_eq!(syllables("queue"), 1);
+        assert_eq!(syllables("readability"), 5);
+        assert_eq!(syllables("Rhythm"), 1);
+    }
+
+    #[test]
+    fn syllables_skip_a_silent_e() {
+        assert_eq!(syllables("make"), 1);
+        assert_eq!(syllables("the"), 1);
+        assert_eq!(syllables("table"), 2);
+        assert_eq!(syllables("syllable"), 3);
+        assert_eq!(syllables("free"), 1);
+    }
+
+    #[test]
+    fn every_word_has_a_syllable() {
+        assert_eq!(syllables("nth"), 1);
+        assert_eq!(syllables(""), 1);
+    }
+
+    #[test]
+    fn strip_markdown_drops_code() {
+        let text = "Some `code` here.\n\n```rust\nlet x = 1;\n```\n\n    indented code\n\nText again.\n";
+        assert_eq!(strip_markdown(text), "Some  here.\n\n\n\nText again.\n");
+        // an indented line straight after a paragraph carries it on
+        assert_eq!(strip_markdown("one\n    two\n"), "one\ntwo\n");
+    }
+
+    #[test]
+    fn strip_markdown_keeps_link_text() {
+        let text = "A [link](https://example.com) and ![an image](a.png), <https://x.y> https://z.w done.\n[1]: https://example.com\n";
+        assert_eq!(strip_markdown(text), "A link and an image,   done.\n");
+    }
+
+    #[test]
+    fn strip_markdown_sets_headings_and_list_items_apart() {
+        let text = "# Title\n- first item\n2. *second* item\n";
+        assert_eq!(strip_markdown(text), "\nTitle\n\n\nfirst item\n\n\nsecond item\n\n");
+        // so each of them ends a sentence of its own
+        assert_eq!(readability(&strip_markdown(text)).sentences, 3);
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Add;
+
+use crate::counter::split_words;
+
+/// The stop words --stop-words=english leaves out, the most common English words which would otherwise fill the top of every list
+pub const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "below", "between", "both", "but", "by", "can", "could", "did", "do",
+    "does", "doing", "down", "during", "each", "few", "for", "from", "further", "had", "has", "have", "having", "he",
+    "her", "here", "hers", "herself", "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "its",
+    "itself", "just", "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on", "once",
+    "only", "or", "other", "our", "ours", "ourselves", "out", "over", "own", "same", "she", "should", "so", "some",
+    "such", "than", "that", "the", "their", "theirs", "them", "themselves", "then", "there", "these", "they", "this",
+    "those", "through", "to", "too", "under", "until", "up", "very", "was", "we", "were", "what", "when", "where",
+    "which", "while", "who", "whom", "why", "will", "with", "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+/// How the words are picked out and which of them are worth keeping track of
+#[derive(Default, Clone)]
+pub struct Vocabulary {
+    /// Split words by the Unicode word boundary rules rather than by whitespace, the same as Fields::unicode_words
+    pub unicode_words: bool,
+    /// Count "The" and "the" as the same word
+    pub fold_case: bool,
+    /// Words left out of the frequencies, matched whatever their case. They're lowercase
+    pub stop_words: HashSet<String>,
+}
+
+/// What the words in some text add up to: how many there are, how long they are and how often each one comes up
+#[derive(Default, Clone)]
+pub struct WordStats {
+    pub lines: usize,
+    pub words: usize,
+    /// The characters in every word without the punctuation around it, and how many words that was
+    /// since a word of nothing but punctuation has no length to speak of
+    pub letters: usize,
+    pub measured_words: usize,
+    pub frequencies: HashMap<String, usize>,
+}
+
+impl Add for WordStats {
+    type Output = WordStats;
+
+    fn add(mut self, other: WordStats) -> WordStats {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.letters += other.letters;
+        self.measured_words += other.measured_words;
+        for (word, count) in other.frequencies {
+            *self.frequencies.entry(word).or_default() += count;
+        }
+        self
+    }
+}
+
+impl WordStats {
+    /// How many different words there are, not counting stop words
+    pub fn unique_words(&self) -> usize {
+        self.frequencies.len()
+    }
+
+    /// The average number of characters in a word
+    pub fn average_word_length(&self) -> f64 {
+        if self.measured_words == 0 {
+            0.0
+        } else {
+            self.letters as f64 / self.measured_words as f64
+        }
+    }
+
+    /// The average number of words on a line, blank lines included
+    pub fn words_per_line(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.words as f64 / self.lines as f64
+        }
+    }
+
+    /// The n most frequent words with how often each comes up, words that come up as often as each other in alphabetical order
+    pub fn top_words(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut words: Vec<(&str, usize)> = self.frequencies.iter().map(|(word, count)| (word.as_str(), *count)).collect();
+        words.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        words.truncate(n);
+        words
+    }
+}
+
+/// Work out the word statistics for some text. The words are the ones the word count counts,
+/// only they're looked up without the punctuation around them so "end." and "end" are the same word
+pub fn word_stats(text: &str, vocabulary: &Vocabulary) -> WordStats {
+    let mut stats = WordStats { lines: text.lines().count(), ..WordStats::default() };
+    for word in split_words(text, vocabulary.unicode_words) {
+        stats.words += 1;
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if word.is_empty() {
+            continue;
+        }
+        stats.letters += word.chars().count();
+        stats.measured_words += 1;
+
+        let lowercase = word.to_lowercase();
+        if vocabulary.stop_words.contains(&lowercase) {
+            continue;
+        }
+        let word = if vocabulary.fold_case { lowercase } else { word.to_string() };
+        *stats.frequencies.entry(word).or_default() += 1;
+    }
+    stats
+}
@@ -0,0 +1,88 @@
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+use crate::counter::{for_each_text, Counts};
+
+/// How much of the start of a file is looked at to decide whether it is binary and how its text is encoded
+pub const SAMPLE_SIZE: usize = 8192;
+
+/// What the start of a file says it holds
+#[derive(Clone, Copy, PartialEq)]
+pub enum Content {
+    /// Text in the given encoding, None for UTF-8 read as it is
+    Text(Option<&'static Encoding>),
+    /// Anything else, only its bytes are worth counting
+    Binary,
+}
+
+/// What counting something found
+#[derive(Clone, Copy)]
+pub enum Counted {
+    /// Text, with every count that was asked for
+    Text(Counts),
+    /// A binary file, only its bytes were counted
+    Binary(Counts),
+}
+
+impl Counted {
+    /// The counts, whichever kind of file they're from
+    pub fn counts(&self) -> Counts {
+        match self {
+            Counted::Text(counts) | Counted::Binary(counts) => *counts,
+        }
+    }
+}
+
+/// Work out what a file holds from a sample of its start. A BOM or an encoding the file is known to be in means it's
+/// text, otherwise text that isn't valid UTF-8 and has no BOM is most likely a legacy Windows file
+pub fn detect(sample: &[u8], encoding: Option<&'static Encoding>) -> Content {
+    let bom = Encoding::for_bom(sample).map(|(encoding, _)| encoding);
+    if encoding.is_none() && bom.is_none() && looks_binary(sample) {
+        return Content::Binary;
+    }
+    let fallback = if invalid_utf8_bytes(sample) > 0 { Some(WINDOWS_1252) } else { None };
+    Content::Text(encoding.or(bom).or(fallback))
+}
+
+/// Decode the whole of a file as text in the encoding detect gave it, dropping any BOM. In UTF-8 that turns out part
+/// way through not to be UTF-8 after all, the bytes that aren't are read as Windows-1252 the same way counting reads them
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> String {
+    match encoding {
+        Some(encoding) => encoding.decode(bytes).0.into_owned(),
+        None => {
+            let mut text = String::with_capacity(bytes.len());
+            for_each_text(bytes, |piece| text.push_str(piece));
+            text
+        }
+    }
+}
+
+// Function to guess whether a sample of a file is binary: text never has NUL bytes. Text without any other control
+// characters is read as Windows-1252 when it isn't UTF-8, but with them only text that's mostly valid UTF-8
+// passes, so more than one invalid byte in ten gives it away
+fn looks_binary(sample: &[u8]) -> bool {
+    if sample.contains(&0) {
+        return true;
+    }
+    // tabs, line and page breaks, the escapes that colour terminal output and the ^Z old DOS files end with are all text
+    let has_controls = sample
+        .iter()
+        .any(|&byte| (byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r' | 0x1a | 0x1b)) || byte == 0x7f);
+    has_controls && invalid_utf8_bytes(sample) * 10 > sample.len()
+}
+
+// Function to count the bytes in a sample that aren't part of a valid UTF-8 character
+fn invalid_utf8_bytes(sample: &[u8]) -> usize {
+    let mut invalid = 0;
+    let mut rest = sample;
+    while let Err(e) = std::str::from_utf8(rest) {
+        match e.error_len() {
+            Some(len) => {
+                invalid += len;
+                rest = &rest[e.valid_up_to() + len..];
+            }
+            // a character cut off at the end of the sample isn't a sign of anything
+            None => break,
+        }
+    }
+    invalid
+}
@@ -0,0 +1,210 @@
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{Walk, WalkBuilder};
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+
+use crate::counter::{count_fields, Counts, Fields};
+use crate::text::{detect, Content, Counted, SAMPLE_SIZE};
+
+/// What to count and which files a walk picks up, the same choices count_lwc's flags make
+pub struct Counter {
+    pub fields: Fields,
+    /// Read text in this encoding instead of detecting it, like --encoding
+    pub encoding: Option<&'static Encoding>,
+    /// Count hidden files and directories too
+    pub hidden: bool,
+    /// Don't respect .gitignore and .ignore files
+    pub no_ignore: bool,
+    /// Globs files have to match to be counted
+    pub include: Vec<String>,
+    /// Globs that rule files and directories out
+    pub exclude: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+}
+
+impl Default for Counter {
+    fn default() -> Counter {
+        Counter {
+            fields: Fields::all(),
+            encoding: None,
+            hidden: false,
+            no_ignore: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: None,
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl Counter {
+    /// Create a counter that counts everything and walks directories the way count_lwc does by default
+    pub fn new() -> Counter {
+        Counter::default()
+    }
+
+    /// Count whatever a reader holds the way count_lwc does, working out first whether it is binary and how its
+    /// text is encoded. Only the bytes of a binary file are counted
+    pub fn count(&self, reader: impl Read) -> io::Result<Counted> {
+        let mut reader = io::BufReader::with_capacity(SAMPLE_SIZE, reader);
+        match detect(reader.fill_buf()?, self.encoding) {
+            Content::Binary => {
+                let bytes = io::copy(&mut reader, &mut io::sink())? as usize;
+                Ok(Counted::Binary(Counts { bytes, ..Counts::default() }))
+            }
+            Content::Text(encoding) => self.count_text(reader, encoding).map(Counted::Text),
+        }
+    }
+
+    /// Count the fields asked for in text in a known encoding, None for UTF-8. Text in any other encoding is
+    /// transcoded to UTF-8 first and any BOM is dropped, but the bytes are still the ones that were read
+    pub fn count_text(&self, reader: impl Read, encoding: Option<&'static Encoding>) -> io::Result<Counts> {
+        let Some(encoding) = encoding else {
+            return count_fields(reader, &self.fields);
+        };
+        let mut raw = ByteCounter { inner: reader, bytes: 0 };
+        let decoder = DecodeReaderBytesBuilder::new().encoding(Some(encoding)).strip_bom(true).build(&mut raw);
+        let mut counts = count_fields(decoder, &self.fields)?;
+        // wc counts the bytes in the file, not in the UTF-8 we turned it into
+        counts.bytes = raw.bytes;
+        Ok(counts)
+    }
+
+    /// Count the fields asked for in whatever a reader holds, a binary file only has its bytes counted
+    pub fn count_reader(&self, reader: impl Read) -> io::Result<Counts> {
+        self.count(reader).map(|counted| counted.counts())
+    }
+
+    /// Count the fields asked for in a single file
+    pub fn count_file(&self, path: &Path) -> io::Result<Counts> {
+        self.count_reader(fs::File::open(path)?)
+    }
+
+    /// Walk a directory, counting every file in it one at a time
+    pub fn files(&self, path: &Path) -> io::Result<Files<'_>> {
+        Ok(Files { counter: self, walk: self.walk_builder(path)?.build() })
+    }
+
+    /// Build the --include and --exclude globs into a matcher for paths under a directory
+    pub fn overrides(&self, path: &Path) -> io::Result<Override> {
+        self.overrides_with(path, &[])
+    }
+
+    /// Build the globs into a matcher like overrides does, letting files matching any of `also_include` through
+    /// --include as well. Those are for files that aren't counted themselves but hold ones that are, like archives,
+    /// and are only added when there are include globs at all since they'd otherwise rule everything else out
+    pub fn overrides_with(&self, path: &Path, also_include: &[String]) -> io::Result<Override> {
+        // include globs whitelist files, exclude globs are turned into ignore rules with a leading '!'
+        let mut overrides = OverrideBuilder::new(path);
+        for glob in &self.include {
+            overrides.add(glob).map_err(io::Error::other)?;
+        }
+        if !self.include.is_empty() {
+            for glob in also_include {
+                overrides.add(glob).map_err(io::Error::other)?;
+            }
+        }
+        for glob in &self.exclude {
+            overrides.add(&format!("!{}", glob)).map_err(io::Error::other)?;
+        }
+        overrides.build().map_err(io::Error::other)
+    }
+
+    /// Set up a walk of a directory, skipping whatever .gitignore, .ignore, hidden files and the globs rule out
+    pub fn walk_builder(&self, path: &Path) -> io::Result<WalkBuilder> {
+        let mut builder = WalkBuilder::new(path);
+        builder
+            .standard_filters(!self.no_ignore)
+            .hidden(!self.hidden)
+            // .gitignore files are honoured even when the directory isn't a git checkout
+            .require_git(false)
+            // the walk keeps track of the directories it's in, so a link back to one of them is reported rather than followed
+            .follow_links(self.follow_symlinks)
+            .max_depth(self.max_depth)
+            .overrides(self.overrides(path)?);
+        if !self.no_ignore {
+            // hidden files are opt in, but the repository's own .git directory is never worth counting
+            builder.filter_entry(|entry| entry.file_name() != ".git");
+        }
+        Ok(builder)
+    }
+}
+
+/// Count everything wc counts in whatever a reader holds, the same way count_lwc counts a file: text in another
+/// encoding is detected from its BOM or read as Windows-1252, and a binary file only has its bytes counted.
+/// count_fields counts text already known to be UTF-8 without looking
+pub fn count_reader(reader: impl Read) -> io::Result<Counts> {
+    Counter::new().count_reader(reader)
+}
+
+/// Iterator over the files under a directory along with their counts, or why they couldn't be counted
+pub struct Files<'a> {
+    counter: &'a Counter,
+    walk: Walk,
+}
+
+impl Iterator for Files<'_> {
+    type Item = (PathBuf, io::Result<Counts>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.walk.next()? {
+                Ok(entry) => {
+                    // directories are walked into rather than counted, and links are only followed when asked to
+                    if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+                        continue;
+                    }
+                    let counts = self.counter.count_file(entry.path());
+                    return Some((entry.into_path(), counts));
+                }
+                Err(e) => return Some((error_path(&e), Err(io::Error::other(e)))),
+            }
+        }
+    }
+}
+
+// Reader that keeps track of how many bytes went through it, so transcoded files still report their size on disk
+struct ByteCounter<R> {
+    inner: R,
+    bytes: usize,
+}
+
+impl<R: Read> Read for ByteCounter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.bytes += read;
+        Ok(read)
+    }
+}
+
+// Function to find the path a walk error happened on, empty for errors that aren't about any one path
+fn error_path(error: &ignore::Error) -> PathBuf {
+    match error {
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => error_path(err),
+        ignore::Error::WithPath { path, .. } => path.clone(),
+        ignore::Error::Loop { child, .. } => child.clone(),
+        _ => PathBuf::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_reader_detects_the_encoding() {
+        // "hé\n" in UTF-16 with a BOM
+        let counts = count_reader(&b"\xff\xfeh\0\xe9\0\n\0"[..]).unwrap();
+        assert_eq!((counts.lines, counts.words, counts.chars, counts.bytes), (1, 1, 3, 8));
+    }
+
+    #[test]
+    fn count_reader_only_counts_the_bytes_of_binary_data() {
+        let counts = count_reader(&b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"[..]).unwrap();
+        assert_eq!((counts.lines, counts.words, counts.chars, counts.bytes), (0, 0, 0, 16));
+    }
+}
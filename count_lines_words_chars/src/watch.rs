@@ -0,0 +1,75 @@
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::output::{self, Format};
+use crate::{run, Options, Totals};
+
+// How long things have to stay quiet after a change before counting again, so saving a file
+// or checking out a branch gets counted once rather than once for every file it touches
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+// The longest the counts wait for things to settle, so a file written to all the time, like a log,
+// still gets counted again this often rather than only once the writes stop
+const MAX_SETTLE_TIME: Duration = Duration::from_secs(1);
+
+// Function to watch the paths for --watch, counting everything again and reporting how much the total has
+// changed since the start each time something under them is created, changed or removed. Only returns on an error
+pub fn watch(paths: &[PathBuf], format: Format, options: &mut Options, start: Totals) -> io::Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+
+    // editors often save by writing a new file and renaming it over the old one, which a watch on
+    // the file itself wouldn't see, so a file is watched through the directory it's in
+    let mut targets = Vec::new();
+    for path in paths {
+        let path = std::path::absolute(path)?;
+        if path.is_dir() {
+            watcher.watch(&path, RecursiveMode::Recursive).map_err(io::Error::other)?;
+        } else {
+            let directory = path.parent().unwrap_or(Path::new("/"));
+            watcher.watch(directory, RecursiveMode::NonRecursive).map_err(io::Error::other)?;
+        }
+        targets.push(path);
+    }
+    // saving the cache after each count mustn't count as a change when it's kept somewhere being watched
+    let cache_dir = match &options.cache {
+        Some(cache) => Some(std::path::absolute(cache.file().parent().unwrap_or(Path::new("")))?),
+        None => None,
+    };
+    let is_change = |event: &notify::Event| {
+        // reading the files to count them is an event too, as is the access time changing
+        let kind = matches!(event.kind, EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_))
+            && !matches!(event.kind, EventKind::Modify(ModifyKind::Metadata(_)));
+        kind && event.paths.iter().any(|path| {
+            targets.iter().any(|target| path.starts_with(target))
+                && !cache_dir.as_ref().is_some_and(|cache_dir| path.starts_with(cache_dir))
+        })
+    };
+
+    loop {
+        match events.recv() {
+            Ok(Ok(event)) if is_change(&event) => {}
+            Ok(Ok(_)) => continue,
+            Ok(Err(e)) => return Err(io::Error::other(e)),
+            Err(e) => return Err(io::Error::other(e)),
+        }
+        let changed = Instant::now();
+        while changed.elapsed() < MAX_SETTLE_TIME && events.recv_timeout(SETTLE_TIME).is_ok() {}
+
+        // on a terminal the counts are redrawn in place, anywhere else each count follows the last
+        if io::stdout().is_terminal() {
+            print!("\x1b[2J\x1b[H");
+        } else {
+            println!();
+        }
+        let now = run(paths, format, options);
+        // --code, --stats and --readability work out something other than the columns the change is shown in
+        if !options.code && options.stats.is_none() && !options.readability {
+            output::print_change(&start, &now.totals, format, options);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use std::fs;
+use std::path::Path;
+
+// A single phone number pulled out of an address book entry
+pub struct ContactNumber {
+    pub name: String,
+    pub kind: String,
+    pub number: String,
+}
+
+// The address book formats we know how to import
+pub enum ContactFormat {
+    VCard,
+    Ldif,
+}
+
+// Pick the contact format from the file extension, None means a plain phone list
+pub fn detect_format(path: &Path) -> Option<ContactFormat> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "vcf" | "vcard" => Some(ContactFormat::VCard),
+        "ldif" => Some(ContactFormat::Ldif),
+        _ => None,
+    }
+}
+
+// Read every phone number out of the given address book file
+pub fn read_contacts(path: &Path, format: ContactFormat) -> Result<Vec<ContactNumber>, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("[ERROR] {}", e))?;
+    Ok(match format {
+        ContactFormat::VCard => parse_vcard(&contents),
+        ContactFormat::Ldif => parse_ldif(&contents),
+    })
+}
+
+// Parse the TEL properties of every BEGIN:VCARD ... END:VCARD block
+fn parse_vcard(contents: &str) -> Vec<ContactNumber> {
+    let mut numbers = Vec::new();
+    let mut name = String::new();
+    let mut card_numbers: Vec<(String, String)> = Vec::new();
+
+    for line in unfold_lines(contents, false) {
+        // everything before the first unquoted ':' is the property name and its parameters
+        let Some((head, value)) = split_property(&line) else {
+            continue;
+        };
+        let mut params = head.split(';');
+        let property = params.next().unwrap_or("");
+        // grouped properties look like "item1.TEL", we only care about the part after the dot
+        let property = property.rsplit('.').next().unwrap_or(property).to_ascii_uppercase();
+
+        match property.as_str() {
+            "BEGIN" if value.trim().eq_ignore_ascii_case("VCARD") => {
+                name.clear();
+                card_numbers.clear();
+            }
+            "FN" => name = unescape_vcard(value.trim()),
+            // only fall back to the structured name when there is no formatted one
+            "N" if name.is_empty() => {
+                let parts: Vec<String> = value.split(';').map(unescape_vcard).collect();
+                let given = parts.get(1).map(String::as_str).unwrap_or("");
+                let family = parts.first().map(String::as_str).unwrap_or("");
+                name = format!("{} {}", given, family).trim().to_string();
+            }
+            "TEL" => {
+                let mut kinds = Vec::new();
+                for param in params {
+                    // vCard 2.1 allows bare types like "TEL;CELL:", later versions use TYPE=
+                    let types = match param.split_once('=') {
+                        Some((key, types)) if key.eq_ignore_ascii_case("TYPE") => types,
+                        Some(_) => continue,
+                        None => param,
+                    };
+                    for kind in types.trim_matches('"').split(',') {
+                        if !kind.is_empty() {
+                            kinds.push(kind.to_ascii_lowercase());
+                        }
+                    }
+                }
+                let kind = if kinds.is_empty() { "tel".to_string() } else { kinds.join("/") };
+                card_numbers.push((kind, normalize_number(value)));
+            }
+            "END" if value.trim().eq_ignore_ascii_case("VCARD") => {
+                let contact_name = if name.is_empty() { "(unnamed)".to_string() } else { name.clone() };
+                for (kind, number) in card_numbers.drain(..) {
+                    numbers.push(ContactNumber { name: contact_name.clone(), kind, number });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    numbers
+}
+
+// Split a vCard line at the first ':' outside double quotes, parameter values like TYPE="a:b" can contain one
+fn split_property(line: &str) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ':' if !quoted => return Some((&line[..i], &line[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Parse the telephoneNumber and mobile attributes of every LDIF record
+fn parse_ldif(contents: &str) -> Vec<ContactNumber> {
+    let mut numbers = Vec::new();
+    let mut name = String::new();
+    let mut dn = String::new();
+    let mut record_numbers: Vec<(String, String)> = Vec::new();
+
+    // a blank line ends a record, so push a final empty line to flush the last one
+    for line in unfold_lines(contents, true).into_iter().chain(std::iter::once(String::new())) {
+        if line.trim().is_empty() {
+            let contact_name = if !name.is_empty() {
+                name.clone()
+            } else if !dn.is_empty() {
+                dn.clone()
+            } else {
+                "(unnamed)".to_string()
+            };
+            for (kind, number) in record_numbers.drain(..) {
+                numbers.push(ContactNumber { name: contact_name.clone(), kind, number });
+            }
+            name.clear();
+            dn.clear();
+            continue;
+        }
+
+        let Some((attribute, value)) = line.split_once(':') else {
+            continue;
+        };
+        // attribute options such as "cn;lang-en" don't change what the value means
+        let attribute = attribute.split(';').next().unwrap_or(attribute);
+
+        // "attr:: value" holds base64 encoded data, "attr:< url" points somewhere else
+        let value = if let Some(encoded) = value.strip_prefix(':') {
+            match decode_base64(encoded.trim()) {
+                Some(decoded) => decoded,
+                None => continue,
+            }
+        } else if value.starts_with('<') {
+            continue;
+        } else {
+            value.trim().to_string()
+        };
+
+        if attribute.eq_ignore_ascii_case("dn") {
+            dn = value;
+        } else if attribute.eq_ignore_ascii_case("cn") {
+            // a record can carry several cn values, the first one is the display name
+            if name.is_empty() {
+                name = value;
+            }
+        } else if attribute.eq_ignore_ascii_case("displayName") {
+            name = value;
+        } else if attribute.eq_ignore_ascii_case("telephoneNumber") {
+            record_numbers.push(("telephoneNumber".to_string(), normalize_number(&value)));
+        } else if attribute.eq_ignore_ascii_case("mobile") {
+            record_numbers.push(("mobile".to_string(), normalize_number(&value)));
+        }
+    }
+
+    numbers
+}
+
+// Join folded lines back together, a line starting with a space (or tab for vCard) continues the previous one
+fn unfold_lines(contents: &str, skip_comments: bool) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim_end_matches('\r');
+        if skip_comments && line.starts_with('#') {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')) {
+            if let Some(last) = lines.last_mut() {
+                if !last.is_empty() {
+                    last.push_str(rest);
+                    continue;
+                }
+            }
+        }
+        lines.push(line.to_string());
+    }
+    lines
+}
+
+// Turn "tel:+1-271-828-1828" style values into something the phone rules understand
+fn normalize_number(value: &str) -> String {
+    let value = value.trim();
+    let value = value.strip_prefix("tel:").unwrap_or(value);
+    // tel URIs can carry parameters such as ";ext=123" after the number
+    let value = value.split(';').next().unwrap_or(value);
+    match value.strip_prefix("+1") {
+        Some(rest) => rest.trim_start_matches(['-', '.', ' ']).to_string(),
+        None => value.to_string(),
+    }
+}
+
+// Undo the backslash escaping vCard uses for ',', ';' and newlines
+fn unescape_vcard(value: &str) -> String {
+    let mut result = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => result.push(' '),
+                Some(other) => result.push(other),
+                None => {}
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result.trim().to_string()
+}
+
+// Decode a standard base64 string, LDIF uses it for values that aren't plain ASCII
+fn decode_base64(encoded: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in encoded.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(numbers: &[ContactNumber]) -> Vec<(&str, &str, &str)> {
+        numbers.iter().map(|n| (n.name.as_str(), n.kind.as_str(), n.number.as_str())).collect()
+    }
+
+    #[test]
+    fn base64_decodes_with_and_without_padding() {
+        assert_eq!(decode_base64("Sm9zw6kgR2FyY8OtYQ=="), Some("José García".to_string()));
+        assert_eq!(decode_base64("YWJj"), Some("abc".to_string()));
+        assert_eq!(decode_base64("YWI"), Some("ab".to_string()));
+        assert_eq!(decode_base64("YW Jj\n"), Some("abc".to_string()));
+        assert_eq!(decode_base64(""), Some(String::new()));
+    }
+
+    #[test]
+    fn base64_rejects_bad_input() {
+        assert_eq!(decode_base64("YW*j"), None);
+        // decodes fine, but the bytes aren't UTF-8
+        assert_eq!(decode_base64("/w=="), None);
+    }
+
+    #[test]
+    fn vcard_reads_every_version() {
+        let contents = "BEGIN:VCARD\nVERSION:3.0\nFN:Jane Doe\nN:Doe;Jane;;;\nTEL;TYPE=CELL:271-828-1828\nTEL;TYPE=WORK,VOICE:(800) 000-5834\nEND:VCARD\n\
+            BEGIN:VCARD\nVERSION:2.1\nN:Smith;John\nTEL;HOME:234-411-9999\nEND:VCARD\n\
+            BEGIN:VCARD\nVERSION:4.0\nFN:Acme\\, Inc.\nTEL;TYPE=\"work,voice\";VALUE=uri:tel:+1-293-453-6789;ext=12\nEND:VCARD\n";
+        assert_eq!(
+            summary(&parse_vcard(contents)),
+            vec![
+                ("Jane Doe", "cell", "271-828-1828"),
+                ("Jane Doe", "work/voice", "(800) 000-5834"),
+                ("John Smith", "home", "234-411-9999"),
+                ("Acme, Inc.", "work/voice", "293-453-6789"),
+            ]
+        );
+    }
+
+    #[test]
+    fn vcard_unfolds_grouped_properties() {
+        let contents = "BEGIN:VCARD\r\nitem1.TEL;TYPE=fax:293.453\r\n .6790\r\nTEL:555-0100\r\nEND:VCARD\r\n";
+        assert_eq!(summary(&parse_vcard(contents)), vec![("(unnamed)", "fax", "293.453.6790"), ("(unnamed)", "tel", "555-0100")]);
+    }
+
+    #[test]
+    fn vcard_splits_at_the_first_unquoted_colon() {
+        assert_eq!(split_property("TEL;TYPE=\"a:b\":555-0100"), Some(("TEL;TYPE=\"a:b\"", "555-0100")));
+        assert_eq!(split_property("TEL:tel:555-0100"), Some(("TEL", "tel:555-0100")));
+        assert_eq!(split_property("no colon"), None);
+
+        let contents = "BEGIN:VCARD\nFN:Jane\nTEL;TYPE=\"x-a:b\":555-0100\nEND:VCARD\n";
+        assert_eq!(summary(&parse_vcard(contents)), vec![("Jane", "x-a:b", "555-0100")]);
+    }
+
+    #[test]
+    fn vcard_skips_numbers_outside_a_card() {
+        assert!(parse_vcard("TEL:555-0100\n").is_empty());
+    }
+
+    #[test]
+    fn ldif_names_records_by_cn_or_dn() {
+        let contents = "# comment\nversion: 1\n\ndn: cn=Jane Doe,dc=example,dc=com\ncn: Jane Doe\ncn: J. Doe\ntelephoneNumber: 271-828-1828\nmobile: 855 800 0000\n\n\
+            dn: cn=Jose,dc=example,dc=com\ncn:: Sm9zw6kgR2FyY8OtYQ==\ntelephoneNumber: +1 234-411-9999\n\n\
+            dn: uid=nobody,dc=exam\n ple,dc=com\nmobile: 161-803-3988";
+        assert_eq!(
+            summary(&parse_ldif(contents)),
+            vec![
+                ("Jane Doe", "telephoneNumber", "271-828-1828"),
+                ("Jane Doe", "mobile", "855 800 0000"),
+                ("José García", "telephoneNumber", "234-411-9999"),
+                ("uid=nobody,dc=example,dc=com", "mobile", "161-803-3988"),
+            ]
+        );
+    }
+
+    #[test]
+    fn ldif_skips_values_it_cannot_read() {
+        let contents = "dn: cn=Jane\ntelephoneNumber:< file:///tmp/number\nmobile:: ***\ntelephoneNumber: 271-828-1828\n";
+        assert_eq!(summary(&parse_ldif(contents)), vec![("cn=Jane", "telephoneNumber", "271-828-1828")]);
+    }
+}
@@ -4,10 +4,16 @@ use std::io::{self, BufRead};
 use std::path::Path;
 use regex::Regex;
 
+mod contacts;
+
 fn main() {
     match validate_phone_list_file() {
         Ok(file_path) => {
-            process_phone_numbers(file_path);
+            // address books are read contact by contact, anything else is a plain phone list
+            match contacts::detect_format(Path::new(&file_path)) {
+                Some(format) => process_contacts(file_path, format),
+                None => process_phone_numbers(file_path),
+            }
         }
         Err(error) => {
             eprintln!("{error}");
@@ -61,9 +67,7 @@ fn process_phone_numbers(file_path: String) {
     let file = fs::File::open(path).unwrap();
     let reader = io::BufReader::new(file);
 
-    // Updated regex to handle leading/trailing whitespaces correctly
-    let phone_re = Regex::new(r"^\s*(?:(\(\d{3}\) \d{3}-\d{4})|(\d{3}-\d{3}-\d{4})|(\d{3}\.\d{3}\.\d{4})|(\d{3} \d{3} \d{4})|(\d{10}))\s*$").unwrap();
-    let toll_free_re = Regex::new(r"^\s*(?:\(?8(?:00|33|44|55|66|77|88)\)?[-.\s]?\d{3}[-.\s]?\d{4})\s*$").unwrap();
+    let (phone_re, toll_free_re) = phone_regexes();
 
     let mut valid_numbers = Vec::new();
     let mut invalid_numbers = Vec::new();
@@ -75,30 +79,92 @@ fn process_phone_numbers(file_path: String) {
             continue;
         }
 
-        if let Some(caps) = phone_re.captures(&line) {
-            let phone_number = caps.get(0).unwrap().as_str().to_string().trim().to_string();
+        match check_phone_number(&line, &phone_re, &toll_free_re) {
+            Ok(lines) => valid_numbers.extend(lines),
+            Err(e) => invalid_numbers.push(format!("[ERROR] {}", e)),
+        }
+    }
+
+    print_results(valid_numbers, invalid_numbers);
+}
+
+// Process the phone numbers found in a vCard or LDIF address book
+fn process_contacts(file_path: String, format: contacts::ContactFormat) {
+    let numbers = match contacts::read_contacts(Path::new(&file_path), format) {
+        Ok(numbers) => numbers,
+        Err(error) => {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+    };
+
+    if numbers.is_empty() {
+        eprintln!("[ERROR] File \"{}\" has no phone numbers in it.", file_path);
+        std::process::exit(1);
+    }
+
+    let (phone_re, toll_free_re) = phone_regexes();
+
+    let mut valid_numbers = Vec::new();
+    let mut invalid_numbers = Vec::new();
 
-            match validate_phone_number(&phone_number) {
-                Ok(_) => {
-                    let cleaned_number = clean_phone_number(&phone_number);
-                    if toll_free_re.is_match(&phone_number) {
-                        valid_numbers.push(format!("{} \x1b[36mis a toll-free phone number\x1b[0m", phone_number));
+    for contact in numbers {
+        // every result is keyed by who the number belongs to and which property it came from
+        let key = format!("{} ({})", contact.name, contact.kind);
+        match check_phone_number(&contact.number, &phone_re, &toll_free_re) {
+            Ok(lines) => {
+                for (i, line) in lines.into_iter().enumerate() {
+                    if i == 0 {
+                        valid_numbers.push(format!("{}: {}", key, line));
                     } else {
-                        valid_numbers.push(phone_number.clone());
+                        // line the international format up under the number
+                        valid_numbers.push(format!("{}  {}", " ".repeat(key.chars().count()), line));
                     }
-
-                    let international_number = format!("+1{}", cleaned_number);
-                    valid_numbers.push(format!("{} international format: {}", "*".repeat(phone_number.len()), international_number));
-                }
-                Err(e) => {
-                    invalid_numbers.push(format!("[ERROR] Phone number {} {}", phone_number.trim(), e));
                 }
             }
-        } else {
-            invalid_numbers.push(format!("[ERROR] Phone number {} has invalid format/digit(s)", line.trim()));
+            Err(e) => invalid_numbers.push(format!("[ERROR] {}: {}", key, e)),
+        }
+    }
+
+    print_results(valid_numbers, invalid_numbers);
+}
+
+// Build the regexes for the accepted phone formats and for toll-free numbers
+fn phone_regexes() -> (Regex, Regex) {
+    // Updated regex to handle leading/trailing whitespaces correctly
+    let phone_re = Regex::new(r"^\s*(?:(\(\d{3}\) \d{3}-\d{4})|(\d{3}-\d{3}-\d{4})|(\d{3}\.\d{3}\.\d{4})|(\d{3} \d{3} \d{4})|(\d{10}))\s*$").unwrap();
+    let toll_free_re = Regex::new(r"^\s*(?:\(?8(?:00|33|44|55|66|77|88)\)?[-.\s]?\d{3}[-.\s]?\d{4})\s*$").unwrap();
+    (phone_re, toll_free_re)
+}
+
+// Check one phone number, returning the lines to print when it is valid or the reason it isn't
+fn check_phone_number(text: &str, phone_re: &Regex, toll_free_re: &Regex) -> Result<Vec<String>, String> {
+    let Some(caps) = phone_re.captures(text) else {
+        return Err(format!("Phone number {} has invalid format/digit(s)", text.trim()));
+    };
+
+    let phone_number = caps.get(0).unwrap().as_str().to_string().trim().to_string();
+
+    match validate_phone_number(&phone_number) {
+        Ok(_) => {
+            let mut lines = Vec::new();
+            let cleaned_number = clean_phone_number(&phone_number);
+            if toll_free_re.is_match(&phone_number) {
+                lines.push(format!("{} \x1b[36mis a toll-free phone number\x1b[0m", phone_number));
+            } else {
+                lines.push(phone_number.clone());
+            }
+
+            let international_number = format!("+1{}", cleaned_number);
+            lines.push(format!("{} international format: {}", "*".repeat(phone_number.len()), international_number));
+            Ok(lines)
         }
+        Err(e) => Err(format!("Phone number {} {}", phone_number.trim(), e)),
     }
+}
 
+// Print the valid numbers to stdout and the invalid ones to stderr
+fn print_results(valid_numbers: Vec<String>, invalid_numbers: Vec<String>) {
     // Output the valid phone numbers
     println!("The output for valid phone numbers is:");
     for number in valid_numbers {